@@ -50,14 +50,14 @@ impl App {
     fn update(&mut self, message: Message) {
         match message {
             Message::TogglePause => {
-                self.video.set_paused(!self.video.paused());
+                self.video.set_paused(!self.video.paused()).expect("pause");
             }
             Message::ToggleLoop => {
                 self.video.set_looping(!self.video.looping());
             }
             Message::Seek(secs) => {
                 self.dragging = true;
-                self.video.set_paused(true);
+                self.video.set_paused(true).expect("pause");
                 self.position = secs;
             }
             Message::SeekRelease => {
@@ -65,7 +65,7 @@ impl App {
                 self.video
                     .seek(Duration::from_secs_f64(self.position), false)
                     .expect("seek");
-                self.video.set_paused(false);
+                self.video.set_paused(false).expect("unpause");
             }
             Message::EndOfStream => {
                 println!("end of stream");
@@ -78,7 +78,7 @@ impl App {
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         Column::new()
             .push(
                 Container::new(
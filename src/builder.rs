@@ -0,0 +1,188 @@
+use crate::format::PixelFormat;
+use crate::{Error, TrackKind, Video, VideoLoader};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use std::time::Duration;
+
+/// Configures how a [`Video`] is created, as an alternative to [`Video::new`].
+///
+/// Options which depend on the media (tracks, position) are applied once it has prerolled,
+/// before playback starts, so that nothing plays with the wrong settings.
+#[derive(Debug)]
+pub struct VideoBuilder {
+    uri: url::Url,
+    paused: bool,
+    position: Option<Duration>,
+    volume: Option<f64>,
+    muted: Option<bool>,
+    audio_track: Option<i32>,
+    text_track: Option<i32>,
+    preroll_timeout: Duration,
+    audio_sink: Option<gst::Element>,
+    video_filter: Option<gst::Element>,
+}
+
+impl VideoBuilder {
+    /// Start configuring a video which loads from `uri`.
+    pub fn new(uri: &url::Url) -> Self {
+        VideoBuilder {
+            uri: uri.clone(),
+            paused: false,
+            position: None,
+            volume: None,
+            muted: None,
+            audio_track: None,
+            text_track: None,
+            preroll_timeout: Duration::from_secs(5),
+            audio_sink: None,
+            video_filter: None,
+        }
+    }
+
+    /// Set if the video starts paused (showing the first frame) instead of playing.
+    pub fn paused(self, paused: bool) -> Self {
+        VideoBuilder { paused, ..self }
+    }
+
+    /// Set the position the video starts at.
+    pub fn position(self, position: Duration) -> Self {
+        VideoBuilder {
+            position: Some(position),
+            ..self
+        }
+    }
+
+    /// Set the initial volume multiplier of the audio; see [`Video::set_volume`].
+    pub fn volume(self, volume: f64) -> Self {
+        VideoBuilder {
+            volume: Some(volume),
+            ..self
+        }
+    }
+
+    /// Set if the audio starts muted.
+    pub fn muted(self, muted: bool) -> Self {
+        VideoBuilder {
+            muted: Some(muted),
+            ..self
+        }
+    }
+
+    /// Set the initially selected audio stream; see [`Video::tracks`].
+    pub fn audio_track(self, index: i32) -> Self {
+        VideoBuilder {
+            audio_track: Some(index),
+            ..self
+        }
+    }
+
+    /// Set the initially selected subtitle stream; see [`Video::tracks`].
+    pub fn subtitle_track(self, index: i32) -> Self {
+        VideoBuilder {
+            text_track: Some(index),
+            ..self
+        }
+    }
+
+    /// Set how long to wait for the media to be ready to play before giving up with [`Error::Timeout`].
+    /// The default is 5 seconds.
+    pub fn preroll_timeout(self, timeout: Duration) -> Self {
+        VideoBuilder {
+            preroll_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// Set the element which plays the audio, instead of the automatically chosen one.
+    pub fn audio_sink(self, sink: impl IsA<gst::Element>) -> Self {
+        VideoBuilder {
+            audio_sink: Some(sink.upcast()),
+            ..self
+        }
+    }
+
+    /// Set an element (or bin of elements) which every video frame passes through before being rendered.
+    pub fn video_filter(self, filter: impl IsA<gst::Element>) -> Self {
+        VideoBuilder {
+            video_filter: Some(filter.upcast()),
+            ..self
+        }
+    }
+
+    /// Create the video, blocking until the media is ready to play.
+    pub fn build(self) -> Result<Video, Error> {
+        let (pipeline, app_sink) = self.pipeline()?;
+        self.start(pipeline, app_sink)
+    }
+
+    /// Create the video on a background thread, without blocking; see [`Video::new_async`].
+    pub fn build_async(self) -> VideoLoader {
+        match self.pipeline() {
+            Ok((pipeline, app_sink)) => {
+                VideoLoader::spawn(pipeline.clone(), move || self.start(pipeline, app_sink))
+            }
+            Err(err) => VideoLoader::failed(err),
+        }
+    }
+
+    pub(crate) fn pipeline(&self) -> Result<(gst::Pipeline, gst_app::AppSink), Error> {
+        gst::init()?;
+
+        let video_scale = gst::ElementFactory::make("videoscale").build()?;
+        let video_convert = gst::ElementFactory::make("videoconvert").build()?;
+        let app_sink = gst_app::AppSink::builder()
+            .name("iced_video")
+            .drop(true)
+            .caps(&PixelFormat::caps())
+            .build();
+
+        let video_sink = gst::Bin::builder().name("iced_video_sink").build();
+        video_sink.add_many([&video_scale, &video_convert, app_sink.upcast_ref()])?;
+        gst::Element::link_many([&video_scale, &video_convert, app_sink.upcast_ref()])?;
+        let pad = video_scale.static_pad("sink").ok_or(Error::Caps)?;
+        video_sink.add_pad(&gst::GhostPad::with_target(&pad)?)?;
+
+        let pipeline = gst::ElementFactory::make("playbin")
+            .property("uri", self.uri.as_str())
+            .property("video-sink", &video_sink)
+            .build()?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| Error::Cast)?;
+
+        if let Some(volume) = self.volume {
+            pipeline.set_property("volume", volume);
+        }
+        if let Some(muted) = self.muted {
+            pipeline.set_property("mute", muted);
+        }
+        if let Some(audio_sink) = &self.audio_sink {
+            pipeline.set_property("audio-sink", audio_sink);
+        }
+        if let Some(video_filter) = &self.video_filter {
+            pipeline.set_property("video-filter", video_filter);
+        }
+
+        Ok((pipeline, app_sink))
+    }
+
+    fn start(self, pipeline: gst::Pipeline, app_sink: gst_app::AppSink) -> Result<Video, Error> {
+        let mut video = Video::from_prerolled(pipeline, app_sink, self.preroll_timeout)?;
+
+        if let Some(index) = self.audio_track {
+            video.set_track(TrackKind::Audio, index)?;
+        }
+        if let Some(index) = self.text_track {
+            video.set_track(TrackKind::Text, index)?;
+            // a selected stream is only rendered while subtitles are enabled
+            video.set_subtitles_enabled(true);
+        }
+        if let Some(position) = self.position {
+            video.seek(position, true)?;
+        }
+        video
+            .set_paused(self.paused)
+            .map_err(|err| Video::start_error(&video.pipeline(), err))?;
+        Ok(video)
+    }
+}
@@ -0,0 +1,150 @@
+use crate::metadata::tag_string;
+use gstreamer as gst;
+use std::time::Duration;
+
+/// A chapter within the media's table of contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// Title of the chapter, if tagged.
+    pub title: Option<String>,
+    /// Time at which the chapter starts.
+    pub start: Duration,
+    /// Time at which the chapter ends.
+    pub end: Duration,
+    /// Nested chapters (e.g., scenes within a chapter).
+    pub chapters: Vec<Chapter>,
+}
+
+impl Chapter {
+    /// Builds the chapter tree from a GStreamer table of contents.
+    ///
+    /// Editions (and other non-chapter entries) are flattened such that only chapters remain.
+    /// Chapters without an end time are assumed to end when the next one starts.
+    pub(crate) fn from_toc(toc: &gst::Toc, duration: Duration) -> Vec<Chapter> {
+        let mut chapters = Vec::new();
+        for entry in toc.entries() {
+            collect(&entry, &mut chapters);
+        }
+        fill_ends(&mut chapters, duration);
+        chapters
+    }
+
+    /// Checks if a given time falls within this chapter.
+    pub fn contains(&self, time: Duration) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+fn collect(entry: &gst::TocEntry, out: &mut Vec<Chapter>) {
+    let times = entry.start_stop_times().filter(|(start, _)| *start >= 0);
+
+    match (entry.entry_type(), times) {
+        (gst::TocEntryType::Chapter, Some((start, stop))) => {
+            let start = Duration::from_nanos(start as u64);
+            let mut chapter = Chapter {
+                title: entry
+                    .tags()
+                    .and_then(|tags| tag_string::<gst::tags::Title>(&tags)),
+                start,
+                end: if stop >= 0 {
+                    Duration::from_nanos(stop as u64)
+                } else {
+                    Duration::ZERO
+                },
+                chapters: Vec::new(),
+            };
+            for sub_entry in entry.sub_entries() {
+                collect(&sub_entry, &mut chapter.chapters);
+            }
+            out.push(chapter);
+        }
+        _ => {
+            for sub_entry in entry.sub_entries() {
+                collect(&sub_entry, out);
+            }
+        }
+    }
+}
+
+fn fill_ends(chapters: &mut [Chapter], end: Duration) {
+    chapters.sort_by_key(|chapter| chapter.start);
+    let starts: Vec<_> = chapters
+        .iter()
+        .skip(1)
+        .map(|chapter| chapter.start)
+        .chain(std::iter::once(end))
+        .collect();
+    for (chapter, next_start) in chapters.iter_mut().zip(starts) {
+        if chapter.end <= chapter.start {
+            chapter.end = next_start.max(chapter.start);
+        }
+        let end = chapter.end;
+        fill_ends(&mut chapter.chapters, end);
+    }
+}
+
+/// Finds the index of the top-level chapter containing a given time.
+pub(crate) fn chapter_at(chapters: &[Chapter], time: Duration) -> Option<usize> {
+    chapters.iter().rposition(|chapter| chapter.contains(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: u64, end: u64, chapters: Vec<Chapter>) -> Chapter {
+        Chapter {
+            title: None,
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+            chapters,
+        }
+    }
+
+    #[test]
+    fn fill_ends_uses_next_start_and_media_end() {
+        let mut chapters = vec![
+            chapter(30, 0, vec![]),
+            chapter(0, 0, vec![]),
+            chapter(10, 20, vec![]),
+        ];
+        fill_ends(&mut chapters, Duration::from_secs(60));
+
+        let times: Vec<_> = chapters
+            .iter()
+            .map(|chapter| (chapter.start.as_secs(), chapter.end.as_secs()))
+            .collect();
+        assert_eq!(times, [(0, 10), (10, 20), (30, 60)]);
+    }
+
+    #[test]
+    fn fill_ends_bounds_nested_chapters_by_parent() {
+        let mut chapters = vec![
+            chapter(0, 0, vec![chapter(5, 0, vec![]), chapter(0, 0, vec![])]),
+            chapter(20, 0, vec![]),
+        ];
+        fill_ends(&mut chapters, Duration::from_secs(40));
+
+        let nested: Vec<_> = chapters[0]
+            .chapters
+            .iter()
+            .map(|chapter| (chapter.start.as_secs(), chapter.end.as_secs()))
+            .collect();
+        assert_eq!(nested, [(0, 5), (5, 20)]);
+    }
+
+    #[test]
+    fn fill_ends_never_ends_before_start() {
+        let mut chapters = vec![chapter(50, 0, vec![])];
+        fill_ends(&mut chapters, Duration::from_secs(40));
+        assert_eq!(chapters[0].end, Duration::from_secs(50));
+    }
+
+    #[test]
+    fn chapter_at_finds_containing_chapter() {
+        let chapters = vec![chapter(0, 10, vec![]), chapter(10, 20, vec![])];
+        assert_eq!(chapter_at(&chapters, Duration::from_secs(0)), Some(0));
+        assert_eq!(chapter_at(&chapters, Duration::from_secs(10)), Some(1));
+        assert_eq!(chapter_at(&chapters, Duration::from_secs(20)), None);
+    }
+}
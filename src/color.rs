@@ -0,0 +1,435 @@
+use crate::format::PixelFormat;
+use gstreamer as gst;
+use gstreamer_video as gst_video;
+use std::str::FromStr;
+
+/// Luminance of SDR reference white in nits, which HDR content is displayed relative to (per BT.2408).
+pub(crate) const SDR_WHITE_NITS: f32 = 203.0;
+
+/// Function decoding non-linear RGB values into linear light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Transfer {
+    /// The piecewise sRGB curve.
+    Srgb,
+    /// A pure power curve; BT.601/709/2020 video uses 2.4 (the BT.1886 reference display).
+    Gamma(f32),
+    /// SMPTE ST 2084 perceptual quantizer, used by HDR10.
+    Pq,
+    /// ARIB STD-B67 hybrid log-gamma.
+    Hlg,
+}
+
+impl Transfer {
+    /// Get if this is a high dynamic range transfer function.
+    pub(crate) fn is_hdr(self) -> bool {
+        matches!(self, Transfer::Pq | Transfer::Hlg)
+    }
+
+    /// Decodes non-linear RGB into linear light, where `1.0` is SDR reference white.
+    ///
+    /// `luma` holds the luma coefficients of the source primaries, which HLG needs for its system gamma.
+    pub(crate) fn to_linear(self, rgb: [f32; 3], luma: [f32; 3]) -> [f32; 3] {
+        match self {
+            Transfer::Srgb => rgb.map(|value| {
+                if value <= 0.04045 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            }),
+            Transfer::Gamma(gamma) => rgb.map(|value| value.max(0.0).powf(gamma)),
+            Transfer::Pq => rgb.map(|value| pq_to_nits(value) / SDR_WHITE_NITS),
+            Transfer::Hlg => {
+                // inverse OETF to scene light, then the OOTF for a 1000 nit display (system gamma 1.2)
+                let scene = rgb.map(|value| {
+                    if value <= 0.5 {
+                        value * value / 3.0
+                    } else {
+                        (((value - 0.559_910_7) / 0.178_832_77).exp() + 0.284_668_92) / 12.0
+                    }
+                });
+                let y = dot(luma, scene).max(0.0);
+                let gain = 1000.0 * y.powf(0.2) / SDR_WHITE_NITS;
+                scene.map(|c| c * gain)
+            }
+        }
+    }
+}
+
+// SMPTE ST 2084 constants
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+/// SMPTE ST 2084 EOTF, from signal to nits.
+fn pq_to_nits(value: f32) -> f32 {
+    let p = value.max(0.0).powf(1.0 / PQ_M2);
+    10000.0 * ((p - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * p)).powf(1.0 / PQ_M1)
+}
+
+/// SMPTE ST 2084 inverse EOTF, from nits to signal.
+fn nits_to_pq(nits: f32) -> f32 {
+    let y = (nits / 10000.0).max(0.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+}
+
+/// How high dynamic range (PQ or HLG) video is fit into the range of the display.
+///
+/// SDR video is never affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    /// Extended Reinhard; simple, but desaturates highlights.
+    Reinhard,
+    /// Filmic curve by John Hable, with more contrast in the midtones.
+    Hable,
+    /// The ITU-R BT.2390 EETF, which leaves everything below the knee untouched.
+    #[default]
+    Bt2390,
+    /// Pass HDR through unchanged; values above SDR white are only preserved if the surface
+    /// has a floating point format (otherwise [`ToneMapping::Bt2390`] is used instead).
+    Passthrough,
+}
+
+impl ToneMapping {
+    /// Resolves passthrough to a real operator when the output can't hold HDR values.
+    pub(crate) fn resolve(self, hdr_output: bool) -> Self {
+        match self {
+            ToneMapping::Passthrough if !hdr_output => ToneMapping::Bt2390,
+            tone_mapping => tone_mapping,
+        }
+    }
+
+    /// Maps a luminance in `0.0..=peak` (relative to SDR white) into `0.0..=1.0`.
+    pub(crate) fn map(self, luminance: f32, peak: f32) -> f32 {
+        if peak <= 1.0 {
+            return luminance;
+        }
+        match self {
+            ToneMapping::Reinhard => {
+                luminance * (1.0 + luminance / (peak * peak)) / (1.0 + luminance)
+            }
+            ToneMapping::Hable => hable(luminance) / hable(peak),
+            ToneMapping::Bt2390 => {
+                // works in the PQ domain, normalized to the source peak
+                let source_peak = nits_to_pq(peak * SDR_WHITE_NITS);
+                let e1 = nits_to_pq(luminance * SDR_WHITE_NITS) / source_peak;
+                let max_lum = nits_to_pq(SDR_WHITE_NITS) / source_peak;
+                let knee = 1.5 * max_lum - 0.5;
+                let e2 = if e1 < knee {
+                    e1
+                } else {
+                    let t = (e1 - knee) / (1.0 - knee);
+                    let (t2, t3) = (t * t, t * t * t);
+                    (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+                        + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+                        + (-2.0 * t3 + 3.0 * t2) * max_lum
+                };
+                pq_to_nits(e2 * source_peak) / SDR_WHITE_NITS
+            }
+            ToneMapping::Passthrough => luminance,
+        }
+    }
+}
+
+fn hable(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+/// How decoded samples are converted to RGB, as described by the colorimetry in the caps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ColorSpace {
+    /// Rows of the matrix converting (offset) YUV to non-linear RGB, including range expansion.
+    pub(crate) matrix: [[f32; 3]; 3],
+    /// Subtracted from the YUV samples before applying `matrix`.
+    pub(crate) offset: [f32; 3],
+    pub(crate) transfer: Transfer,
+    /// Luma coefficients (Kr, Kg, Kb) of the source.
+    pub(crate) luma: [f32; 3],
+    /// Brightest luminance in the content relative to SDR white; `1.0` for SDR.
+    pub(crate) peak: f32,
+    /// Rows of the matrix converting linear RGB from the source primaries to BT.709/sRGB primaries.
+    pub(crate) gamut: [[f32; 3]; 3],
+}
+
+const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+impl ColorSpace {
+    /// Plain sRGB, which needs no conversion other than linearizing.
+    pub(crate) const SRGB: ColorSpace = ColorSpace {
+        matrix: IDENTITY,
+        offset: [0.0; 3],
+        transfer: Transfer::Srgb,
+        luma: [0.2126, 0.7152, 0.0722],
+        peak: 1.0,
+        gamut: IDENTITY,
+    };
+
+    /// Gets the color space from the `colorimetry` field of negotiated caps.
+    ///
+    /// Falls back to the same defaults as GStreamer when it is missing or unknown:
+    /// sRGB for RGB formats, BT.709 for HD video and BT.601 otherwise.
+    pub(crate) fn from_caps(caps: &gst::CapsRef, format: PixelFormat, height: i32) -> Self {
+        if !format.is_yuv() {
+            return ColorSpace::SRGB;
+        }
+
+        let colorimetry = caps
+            .structure(0)
+            .and_then(|s| s.get::<&str>("colorimetry").ok())
+            .and_then(|colorimetry| gst_video::VideoColorimetry::from_str(colorimetry).ok());
+        let hd = height >= 720;
+
+        // Kr and Kb luma coefficients
+        let (kr, kb) = match colorimetry.as_ref().map(|c| c.matrix()) {
+            Some(gst_video::VideoColorMatrix::Bt709) => (0.2126, 0.0722),
+            Some(gst_video::VideoColorMatrix::Bt601) => (0.299, 0.114),
+            Some(gst_video::VideoColorMatrix::Bt2020) => (0.2627, 0.0593),
+            Some(gst_video::VideoColorMatrix::Smpte240m) => (0.212, 0.087),
+            Some(gst_video::VideoColorMatrix::Fcc) => (0.30, 0.11),
+            _ if hd => (0.2126, 0.0722),
+            _ => (0.299, 0.114),
+        };
+
+        let full_range = matches!(
+            colorimetry.as_ref().map(|c| c.range()),
+            Some(gst_video::VideoColorRange::Range0_255)
+        );
+
+        let transfer = match colorimetry.as_ref().map(|c| c.transfer()) {
+            Some(gst_video::VideoTransferFunction::Srgb) => Transfer::Srgb,
+            Some(gst_video::VideoTransferFunction::Gamma10) => Transfer::Gamma(1.0),
+            Some(gst_video::VideoTransferFunction::Gamma18) => Transfer::Gamma(1.8),
+            Some(gst_video::VideoTransferFunction::Gamma20) => Transfer::Gamma(2.0),
+            Some(gst_video::VideoTransferFunction::Gamma22)
+            | Some(gst_video::VideoTransferFunction::Adobergb) => Transfer::Gamma(2.2),
+            Some(gst_video::VideoTransferFunction::Gamma28) => Transfer::Gamma(2.8),
+            Some(gst_video::VideoTransferFunction::Smpte2084) => Transfer::Pq,
+            Some(gst_video::VideoTransferFunction::AribStdB67) => Transfer::Hlg,
+            _ => Transfer::Gamma(2.4),
+        };
+
+        let peak_nits = match transfer {
+            Transfer::Pq => content_peak_nits(caps).unwrap_or(1000.0),
+            // HLG is scene-referred, so it is always shown on a nominal 1000 nit display
+            Transfer::Hlg => 1000.0,
+            _ => SDR_WHITE_NITS,
+        };
+
+        let gamut = match colorimetry.as_ref().map(|c| c.primaries()) {
+            Some(gst_video::VideoColorPrimaries::Bt2020) => BT2020_TO_BT709,
+            _ => IDENTITY,
+        };
+
+        let (matrix, offset) = yuv_matrix(kr, kb, full_range);
+        ColorSpace {
+            matrix,
+            offset,
+            transfer,
+            luma: [kr, 1.0 - kr - kb, kb],
+            peak: (peak_nits / SDR_WHITE_NITS).max(1.0),
+            gamut,
+        }
+    }
+
+    /// Converts a pixel (with components normalized to `0.0..=1.0`) to linear RGB with BT.709 primaries,
+    /// tone mapping HDR content.
+    pub(crate) fn to_linear(self, pixel: [f32; 3], tone_mapping: ToneMapping) -> [f32; 3] {
+        let pixel: [f32; 3] = std::array::from_fn(|i| pixel[i] - self.offset[i]);
+        let rgb = mul(&self.matrix, pixel).map(|c| c.clamp(0.0, 1.0));
+        let mut rgb = self.transfer.to_linear(rgb, self.luma);
+
+        if self.transfer.is_hdr() {
+            // scale by luminance so that hues are preserved
+            let luminance = dot(self.luma, rgb);
+            if luminance > 0.0 {
+                let scale = tone_mapping.map(luminance, self.peak) / luminance;
+                rgb = rgb.map(|c| c * scale);
+            }
+        }
+
+        mul(&self.gamut, rgb)
+    }
+
+    /// Converts a pixel (with components normalized to `0.0..=1.0`) to 8-bit sRGB, as displayed.
+    pub(crate) fn to_srgb8(self, pixel: [f32; 3], tone_mapping: ToneMapping) -> [u8; 3] {
+        // 8-bit output can't hold HDR
+        let tone_mapping = tone_mapping.resolve(false);
+        self.to_linear(pixel, tone_mapping).map(|c| {
+            let c = c.clamp(0.0, 1.0);
+            let c = if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+            (c * 255.0).round() as u8
+        })
+    }
+}
+
+/// Builds the YUV to RGB matrix for given luma coefficients, expanding limited range to full range.
+fn yuv_matrix(kr: f32, kb: f32, full_range: bool) -> ([[f32; 3]; 3], [f32; 3]) {
+    let kg = 1.0 - kr - kb;
+    let (y_scale, c_scale, y_offset) = if full_range {
+        (1.0, 1.0, 0.0)
+    } else {
+        (255.0 / 219.0, 255.0 / 224.0, 16.0 / 255.0)
+    };
+    let c_offset = 128.0 / 255.0;
+
+    let matrix = [
+        [y_scale, 0.0, 2.0 * (1.0 - kr) * c_scale],
+        [
+            y_scale,
+            -2.0 * kb * (1.0 - kb) / kg * c_scale,
+            -2.0 * kr * (1.0 - kr) / kg * c_scale,
+        ],
+        [y_scale, 2.0 * (1.0 - kb) * c_scale, 0.0],
+    ];
+    (matrix, [y_offset, c_offset, c_offset])
+}
+
+/// Brightest luminance of the content in nits, from the HDR10 static metadata in the caps.
+fn content_peak_nits(caps: &gst::CapsRef) -> Option<f32> {
+    let s = caps.structure(0)?;
+
+    // "maxCLL:maxFALL" in nits
+    let max_cll = s
+        .get::<&str>("content-light-level")
+        .ok()
+        .and_then(|cll| cll.split(':').next()?.parse::<f32>().ok())
+        .filter(|&max_cll| max_cll > 0.0);
+
+    // primaries, white point, then "max_luminance:min_luminance" in units of 0.0001 nits
+    let mastering_max = s
+        .get::<&str>("mastering-display-info")
+        .ok()
+        .and_then(|info| info.split(':').nth(8)?.parse::<f32>().ok())
+        .map(|max| max / 10000.0)
+        .filter(|&max| max > 0.0);
+
+    max_cll.or(mastering_max)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn mul(matrix: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| dot(row, v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BT709: (f32, f32) = (0.2126, 0.0722);
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
+    }
+
+    /// Converts normalized YUV to RGB the same way the shader does.
+    fn to_rgb(yuv: [f32; 3], (kr, kb): (f32, f32), full_range: bool) -> [f32; 3] {
+        let (matrix, offset) = yuv_matrix(kr, kb, full_range);
+        mul(&matrix, std::array::from_fn(|i| yuv[i] - offset[i]))
+    }
+
+    #[test]
+    fn limited_range_black_and_white() {
+        let gray = 128.0 / 255.0;
+        assert_close(to_rgb([16.0 / 255.0, gray, gray], BT709, false), [0.0; 3]);
+        assert_close(to_rgb([235.0 / 255.0, gray, gray], BT709, false), [1.0; 3]);
+    }
+
+    #[test]
+    fn full_range_black_and_white() {
+        let gray = 128.0 / 255.0;
+        assert_close(to_rgb([0.0, gray, gray], BT709, true), [0.0; 3]);
+        assert_close(to_rgb([1.0, gray, gray], BT709, true), [1.0; 3]);
+    }
+
+    #[test]
+    fn primaries_round_trip() {
+        for (kr, kb) in [BT709, (0.299, 0.114), (0.2627, 0.0593)] {
+            for rgb in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] {
+                // forward full range conversion, with chroma centered at 128
+                let y = kr * rgb[0] + (1.0 - kr - kb) * rgb[1] + kb * rgb[2];
+                let cb = (rgb[2] - y) / (2.0 * (1.0 - kb)) + 128.0 / 255.0;
+                let cr = (rgb[0] - y) / (2.0 * (1.0 - kr)) + 128.0 / 255.0;
+                assert_close(to_rgb([y, cb, cr], (kr, kb), true), rgb);
+            }
+        }
+    }
+
+    const OPERATORS: [ToneMapping; 3] = [
+        ToneMapping::Reinhard,
+        ToneMapping::Hable,
+        ToneMapping::Bt2390,
+    ];
+
+    /// A 1000 nit HDR10 master.
+    const PEAK: f32 = 1000.0 / SDR_WHITE_NITS;
+
+    #[test]
+    fn sdr_is_untouched() {
+        for tone_mapping in OPERATORS {
+            for luminance in [0.0, 0.25, 0.5, 1.0] {
+                assert_eq!(tone_mapping.map(luminance, 1.0), luminance);
+            }
+        }
+    }
+
+    #[test]
+    fn operators_span_output_range() {
+        for tone_mapping in OPERATORS {
+            assert!(tone_mapping.map(0.0, PEAK).abs() < 1e-3, "{tone_mapping:?}");
+            assert!(
+                (tone_mapping.map(PEAK, PEAK) - 1.0).abs() < 1e-3,
+                "{tone_mapping:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn operators_are_monotonic() {
+        for tone_mapping in OPERATORS {
+            let mut last = tone_mapping.map(0.0, PEAK);
+            for i in 1..=100 {
+                let mapped = tone_mapping.map(PEAK * i as f32 / 100.0, PEAK);
+                assert!(
+                    mapped + 1e-5 >= last,
+                    "{tone_mapping:?} decreases at step {i}"
+                );
+                last = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn bt2390_keeps_shadows() {
+        let mapped = ToneMapping::Bt2390.map(0.1, PEAK);
+        assert!((mapped - 0.1).abs() < 1e-3, "{mapped}");
+    }
+
+    #[test]
+    fn passthrough_needs_hdr_output() {
+        assert_eq!(ToneMapping::Passthrough.map(PEAK, PEAK), PEAK);
+        assert_eq!(
+            ToneMapping::Passthrough.resolve(true),
+            ToneMapping::Passthrough
+        );
+        assert_eq!(ToneMapping::Passthrough.resolve(false), ToneMapping::Bt2390);
+        assert_eq!(ToneMapping::Hable.resolve(false), ToneMapping::Hable);
+    }
+}
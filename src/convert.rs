@@ -0,0 +1,178 @@
+use crate::color::ToneMapping;
+use crate::frame::{Channel, Frame};
+
+/// How pixels are sampled when a frame is resized on the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Filter {
+    /// Interpolates between the four nearest pixels, like the GPU does when rendering.
+    Bilinear,
+    /// Averages all pixels covered by each resized pixel, which avoids aliasing when downscaling.
+    #[default]
+    Area,
+}
+
+/// The size an image of a frame is converted to, preserving the aspect ratio of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resize {
+    /// Maximum width of the image, or `0` to only fit the height.
+    pub width: u32,
+    /// Maximum height of the image, or `0` to only fit the width.
+    pub height: u32,
+    /// How pixels are sampled.
+    pub filter: Filter,
+}
+
+impl Resize {
+    /// Keeps the resolution of the frame.
+    pub const ORIGINAL: Resize = Resize {
+        width: 0,
+        height: 0,
+        filter: Filter::Bilinear,
+    };
+
+    /// Fits the image within `width` by `height`; either can be `0` to only fit the other.
+    pub fn fit(width: u32, height: u32) -> Self {
+        Resize {
+            width,
+            height,
+            filter: Filter::default(),
+        }
+    }
+
+    /// Set how pixels are sampled.
+    pub fn filter(self, filter: Filter) -> Self {
+        Resize { filter, ..self }
+    }
+
+    /// Get the size of the image for a frame of a given size.
+    pub(crate) fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale_x = self.width as f64 / width as f64;
+        let scale_y = self.height as f64 / height as f64;
+        let scale = match (self.width, self.height) {
+            (0, 0) => return (width, height),
+            (0, _) => scale_y,
+            (_, 0) => scale_x,
+            _ => scale_x.min(scale_y),
+        };
+        (
+            ((width as f64 * scale).round() as u32).max(1),
+            ((height as f64 * scale).round() as u32).max(1),
+        )
+    }
+}
+
+/// Converts a frame to sRGB RGBA, resizing it and spreading the work across all cores.
+///
+/// Colors are converted the same way as on screen (including tone mapping of HDR video to SDR).
+pub(crate) fn to_rgba(
+    frame: &Frame,
+    resize: Resize,
+    tone_mapping: ToneMapping,
+) -> (u32, u32, Vec<u8>) {
+    let (width, height) = resize.size(frame.width, frame.height);
+    let mut rgba = vec![0xFF; width as usize * height as usize * 4];
+
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let rows_per_thread = (height as usize).div_ceil(threads);
+    let scale_x = frame.width as f32 / width as f32;
+    let scale_y = frame.height as f32 / height as f32;
+    let channels = frame.format.channels();
+
+    std::thread::scope(|scope| {
+        for (chunk, rows) in rgba
+            .chunks_mut(rows_per_thread * width as usize * 4)
+            .enumerate()
+        {
+            scope.spawn(move || {
+                let channels = channels.map(|channel| frame.channel(channel));
+                let first_row = chunk * rows_per_thread;
+                for (i, pixel) in rows.chunks_exact_mut(4).enumerate() {
+                    let x = (i % width as usize) as f32;
+                    let y = (first_row + i / width as usize) as f32;
+                    let components = channels.map(|channel| match resize.filter {
+                        Filter::Bilinear => {
+                            bilinear(&channel, (x + 0.5) * scale_x, (y + 0.5) * scale_y)
+                        }
+                        Filter::Area => area(
+                            &channel,
+                            (x * scale_x, y * scale_y),
+                            ((x + 1.0) * scale_x, (y + 1.0) * scale_y),
+                        ),
+                    });
+                    pixel[..3].copy_from_slice(&frame.color.to_srgb8(components, tone_mapping));
+                }
+            });
+        }
+    });
+
+    (width, height, rgba)
+}
+
+/// Interpolates a channel at `(x, y)` in the frame's pixels, where pixel centers are at `.5`.
+fn bilinear(channel: &Channel, x: f32, y: f32) -> f32 {
+    let (sub_x, sub_y) = channel.subsampling;
+    // position in the plane's (possibly subsampled) samples, where sample centers are at integers
+    let px = (x / sub_x as f32 - 0.5).clamp(0.0, (channel.width - 1) as f32);
+    let py = (y / sub_y as f32 - 0.5).clamp(0.0, (channel.height - 1) as f32);
+    let (x0, y0) = (px as u32, py as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(channel.width - 1),
+        (y0 + 1).min(channel.height - 1),
+    );
+    let (tx, ty) = (px.fract(), py.fract());
+
+    let (top_left, top_right) = (channel.at(x0, y0), channel.at(x1, y0));
+    let (bottom_left, bottom_right) = (channel.at(x0, y1), channel.at(x1, y1));
+    let top = top_left + (top_right - top_left) * tx;
+    let bottom = bottom_left + (bottom_right - bottom_left) * tx;
+    top + (bottom - top) * ty
+}
+
+/// Averages a channel over the samples covering the region from `start` to `end` in the frame's pixels.
+fn area(channel: &Channel, start: (f32, f32), end: (f32, f32)) -> f32 {
+    let (sub_x, sub_y) = channel.subsampling;
+    let range = |start: f32, end: f32, sub: u32, size: u32| {
+        let first = ((start / sub as f32) as u32).min(size - 1);
+        let last = ((end / sub as f32).ceil() as u32).clamp(first + 1, size);
+        first..last
+    };
+    let xs = range(start.0, end.0, sub_x, channel.width);
+    let ys = range(start.1, end.1, sub_y, channel.height);
+
+    let mut sum = 0.0;
+    for y in ys.clone() {
+        for x in xs.clone() {
+            sum += channel.at(x, y);
+        }
+    }
+    sum / (xs.len() * ys.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn original_size() {
+        assert_eq!(Resize::ORIGINAL.size(1920, 1080), (1920, 1080));
+    }
+
+    #[test]
+    fn fits_within_both() {
+        assert_eq!(Resize::fit(640, 640).size(1920, 1080), (640, 360));
+        assert_eq!(Resize::fit(640, 240).size(1920, 1080), (427, 240));
+        // upscales too
+        assert_eq!(Resize::fit(3840, 2160).size(1280, 720), (3840, 2160));
+    }
+
+    #[test]
+    fn fits_single_dimension() {
+        assert_eq!(Resize::fit(320, 0).size(1920, 1080), (320, 180));
+        assert_eq!(Resize::fit(0, 100).size(1920, 1080), (178, 100));
+    }
+
+    #[test]
+    fn never_empty() {
+        assert_eq!(Resize::fit(100, 0).size(10000, 10), (100, 1));
+    }
+}
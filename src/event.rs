@@ -0,0 +1,52 @@
+use crate::MissingPlugin;
+use gstreamer as gst;
+use std::time::Duration;
+
+/// Something that happened during playback of a [`Video`](crate::Video).
+///
+/// Received through [`VideoPlayer::on_event`](crate::VideoPlayer::on_event).
+#[derive(Debug, Clone)]
+pub enum VideoEvent {
+    /// A new frame was received.
+    NewFrame,
+    /// The video reached the end of stream (i.e., the video ended).
+    EndOfStream,
+    /// The video looped, given the number of loops so far.
+    Looped(u32),
+    /// Playback entered a different chapter, given the index of the new chapter.
+    ChapterChanged(usize),
+    /// The pipeline changed state (e.g., from paused to playing).
+    StateChanged {
+        /// State before the change.
+        old: gst::State,
+        /// State after the change.
+        new: gst::State,
+    },
+    /// A (potentially fatal) error occurred.
+    Error(glib::Error),
+    /// A non-fatal problem occurred.
+    Warning(glib::Error),
+    /// A network stream reported buffering progress, given the percentage buffered.
+    Buffering(i32),
+    /// New tags were received; [`Video::metadata`](crate::Video::metadata) was updated accordingly.
+    Tags(gst::TagList),
+    /// The resolution of the video changed mid-stream; [`Video::size`](crate::Video::size) was updated accordingly.
+    Resized {
+        /// New width of the video.
+        width: i32,
+        /// New height of the video.
+        height: i32,
+    },
+    /// The duration of the media changed (e.g., because new media started playing).
+    DurationChanged(Duration),
+    /// A seek finished and playback continues from the new position.
+    SeekDone,
+    /// A step from [`Video::step_frames`](crate::Video::step_frames) finished and the new frame is displayed.
+    StepDone,
+    /// The set of streams available in the media changed.
+    StreamCollection(gst::StreamCollection),
+    /// A plugin needed for part of the media (e.g., one of the audio streams) is not installed.
+    MissingPlugin(MissingPlugin),
+    /// An element posted a custom message.
+    Element(gst::Structure),
+}
@@ -0,0 +1,137 @@
+use crate::Error;
+use gstreamer as gst;
+
+/// Raw video formats which are rendered directly, without converting them on the CPU first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum PixelFormat {
+    /// 8-bit 4:2:0 YUV, with a luma plane and an interleaved chroma plane.
+    Nv12,
+    /// 8-bit 4:2:0 YUV, with separate planes for each component.
+    I420,
+    /// 10-bit 4:2:0 YUV, laid out like NV12 with 16-bit little-endian samples.
+    P010,
+    /// 16-bit 4:2:0 YUV, laid out like NV12 with 16-bit little-endian samples.
+    P016,
+    /// 8-bit 4:4:4 YUV, with separate planes for each component.
+    Y444,
+    /// 8-bit RGB with alpha.
+    Rgba,
+    /// 8-bit BGR with alpha.
+    Bgra,
+}
+
+/// Layout of a single plane within a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Plane {
+    /// Offset in bytes of the first visible sample, from the start of the plane's data.
+    pub(crate) offset: usize,
+    /// Bytes per row.
+    pub(crate) stride: usize,
+    /// Width of the plane in samples.
+    pub(crate) width: u32,
+    /// Height of the plane in rows.
+    pub(crate) height: u32,
+    /// Number of interleaved components per sample.
+    pub(crate) components: u32,
+    /// Bytes per component (1 or 2).
+    pub(crate) depth: u32,
+    /// Horizontal and vertical subsampling relative to the full resolution.
+    pub(crate) subsampling: (u32, u32),
+}
+
+impl PixelFormat {
+    /// All formats, in order of preference when negotiating with GStreamer.
+    pub(crate) const ALL: [PixelFormat; 7] = [
+        PixelFormat::Nv12,
+        PixelFormat::I420,
+        PixelFormat::P010,
+        PixelFormat::P016,
+        PixelFormat::Y444,
+        PixelFormat::Rgba,
+        PixelFormat::Bgra,
+    ];
+
+    /// Name of the format in GStreamer caps.
+    pub(crate) fn caps_name(self) -> &'static str {
+        match self {
+            PixelFormat::Nv12 => "NV12",
+            PixelFormat::I420 => "I420",
+            PixelFormat::P010 => "P010_10LE",
+            PixelFormat::P016 => "P016_LE",
+            PixelFormat::Y444 => "Y444",
+            PixelFormat::Rgba => "RGBA",
+            PixelFormat::Bgra => "BGRA",
+        }
+    }
+
+    /// Caps accepting any of the supported formats, for the appsink.
+    pub(crate) fn caps() -> gst::Caps {
+        gst::Caps::builder("video/x-raw")
+            .field(
+                "format",
+                gst::List::new(PixelFormat::ALL.map(PixelFormat::caps_name)),
+            )
+            .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+            .build()
+    }
+
+    /// Gets the format described by negotiated caps.
+    pub(crate) fn from_caps(caps: &gst::CapsRef) -> Result<Self, Error> {
+        let s = caps.structure(0).ok_or(Error::Caps)?;
+        let name = s.get::<&str>("format").map_err(|_| Error::Caps)?;
+        PixelFormat::ALL
+            .into_iter()
+            .find(|format| format.caps_name() == name)
+            .ok_or(Error::Caps)
+    }
+
+    /// Get if the format stores YUV (rather than RGB) samples.
+    pub(crate) fn is_yuv(self) -> bool {
+        !matches!(self, PixelFormat::Rgba | PixelFormat::Bgra)
+    }
+
+    /// Location of the Y, U, and V (or R, G, and B) components as `(plane, component)` pairs.
+    pub(crate) fn channels(self) -> [(usize, usize); 3] {
+        match self {
+            PixelFormat::Nv12 | PixelFormat::P010 | PixelFormat::P016 => [(0, 0), (1, 0), (1, 1)],
+            PixelFormat::I420 | PixelFormat::Y444 => [(0, 0), (1, 0), (2, 0)],
+            PixelFormat::Rgba => [(0, 0), (0, 1), (0, 2)],
+            PixelFormat::Bgra => [(0, 2), (0, 1), (0, 0)],
+        }
+    }
+
+    /// Layout of each plane of a tightly packed frame, relative to the start of that plane.
+    pub(crate) fn planes(self, width: u32, height: u32) -> Vec<Plane> {
+        let (components, depth, chroma_components, chroma_planes, subsampling) = match self {
+            PixelFormat::Nv12 => (1, 1, 2, 1, (2, 2)),
+            PixelFormat::P010 | PixelFormat::P016 => (1, 2, 2, 1, (2, 2)),
+            PixelFormat::I420 => (1, 1, 1, 2, (2, 2)),
+            PixelFormat::Y444 => (1, 1, 1, 2, (1, 1)),
+            PixelFormat::Rgba | PixelFormat::Bgra => (4, 1, 0, 0, (1, 1)),
+        };
+        let chroma_width = width.div_ceil(subsampling.0);
+        let chroma_height = height.div_ceil(subsampling.1);
+
+        let mut planes = vec![Plane {
+            offset: 0,
+            stride: (width * components * depth) as usize,
+            width,
+            height,
+            components,
+            depth,
+            subsampling: (1, 1),
+        }];
+        for _ in 0..chroma_planes {
+            planes.push(Plane {
+                offset: 0,
+                stride: (chroma_width * chroma_components * depth) as usize,
+                width: chroma_width,
+                height: chroma_height,
+                components: chroma_components,
+                depth,
+                subsampling,
+            });
+        }
+        planes
+    }
+}
@@ -0,0 +1,510 @@
+use crate::color::ColorSpace;
+use crate::format::{PixelFormat, Plane};
+use crate::video::video_format;
+use crate::Error;
+use gstreamer as gst;
+use gstreamer_video as gst_video;
+use gstreamer_video::prelude::*;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Number of frames which can be queued ahead of the one being displayed.
+const QUEUE_CAPACITY: usize = 4;
+
+/// Most frames of a video which are read in place from the decoder's buffers at once, counting queued,
+/// displayed, and tapped frames; any more are copied, so the decoder gets its buffer back right away.
+///
+/// Decoders (especially hardware ones) only have a few buffers in their pool, and stall once all are held.
+pub(crate) const MAX_PINNED_FRAMES: usize = 4;
+
+/// Released once the frame holding on to a decoder buffer is dropped.
+#[derive(Debug)]
+struct Pin(Arc<AtomicUsize>);
+
+impl Drop for Pin {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A decoded frame, read in place from the GStreamer buffer (which is kept alive until the frame is dropped).
+#[derive(Debug)]
+pub(crate) struct Frame {
+    frame: gst_video::VideoFrame<gst_video::video_frame::Readable>,
+    pub(crate) format: PixelFormat,
+    pub(crate) color: ColorSpace,
+    /// Size of the visible (cropped) region.
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Layout of the visible region within each plane of the buffer.
+    pub(crate) planes: Vec<Plane>,
+    /// Presentation timestamp of the frame in the stream.
+    pub(crate) pts: Option<gst::ClockTime>,
+    /// Position of the frame in the media (i.e., its stream time).
+    pub(crate) position: Option<gst::ClockTime>,
+    /// Running time of the pipeline at which the frame should be displayed.
+    pub(crate) due: Option<gst::ClockTime>,
+    pub(crate) duration: Option<gst::ClockTime>,
+    pub(crate) keyframe: bool,
+    /// Framerate negotiated along with the frame, which is `0/1` for variable framerates.
+    pub(crate) framerate: gst::Fraction,
+    /// Held while the frame reads a buffer of the decoder's pool in place.
+    pin: Option<Pin>,
+}
+
+/// Timing of a single frame of the video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameInfo {
+    /// Presentation timestamp of the frame.
+    pub pts: Duration,
+    /// Position of the frame in the media, like [`Video::position`](crate::Video::position).
+    ///
+    /// This differs from `pts` when timestamps don't start at zero (e.g., in MPEG-TS).
+    pub position: Duration,
+    /// Index of the frame, counting from the start of the media at the negotiated framerate.
+    pub index: u64,
+    /// How long the frame is displayed for, if known.
+    pub duration: Option<Duration>,
+    /// Whether the frame can be decoded independently of others (e.g., an I-frame).
+    pub keyframe: bool,
+}
+
+impl Frame {
+    /// Maps a sample from the appsink, using its real plane strides and offsets and honoring crop metadata.
+    ///
+    /// Like the sink does when syncing to the clock, `latency` is added to the running time of the frame.
+    pub(crate) fn new(sample: &gst::Sample, latency: gst::ClockTime) -> Result<Self, Error> {
+        let caps = sample.caps().ok_or(Error::Caps)?;
+        let (format, info) = video_format(caps)?;
+        let buffer = sample.buffer_owned().ok_or(Error::Caps)?;
+
+        let (crop_x, crop_y, width, height) = buffer
+            .meta::<gst_video::VideoCropMeta>()
+            .map(|crop| crop.rect())
+            .unwrap_or((0, 0, info.width(), info.height()));
+
+        let pts = buffer.pts();
+        let duration = buffer.duration();
+        let keyframe = !buffer.flags().contains(gst::BufferFlags::DELTA_UNIT);
+        let segment = sample
+            .segment()
+            .and_then(|segment| segment.downcast_ref::<gst::ClockTime>())
+            .zip(pts);
+        let due = segment
+            .and_then(|(segment, pts)| segment.to_running_time(pts))
+            .map(|running_time| running_time + latency);
+        let position = segment
+            .and_then(|(segment, pts)| segment.to_stream_time(pts))
+            .or(pts);
+
+        let frame =
+            gst_video::VideoFrame::from_buffer_readable(buffer, &info).map_err(|_| Error::Caps)?;
+
+        let planes = format
+            .planes(width, height)
+            .into_iter()
+            .enumerate()
+            .map(|(i, plane)| {
+                let stride = frame.plane_stride()[i] as usize;
+                let pixel_size = (plane.components * plane.depth) as usize;
+                let (sub_x, sub_y) = plane.subsampling;
+                Plane {
+                    offset: (crop_y / sub_y) as usize * stride
+                        + (crop_x / sub_x) as usize * pixel_size,
+                    stride,
+                    ..plane
+                }
+            })
+            .collect();
+
+        Ok(Frame {
+            color: ColorSpace::from_caps(caps, format, info.height() as _),
+            frame,
+            format,
+            width,
+            height,
+            planes,
+            pts,
+            position,
+            due,
+            duration,
+            keyframe,
+            framerate: info.fps(),
+            pin: None,
+        })
+    }
+
+    /// Creates a frame for playback, which reads the buffer in place unless [`MAX_PINNED_FRAMES`] already do
+    /// (as counted by `pins`), in which case the buffer is copied.
+    pub(crate) fn pinned(
+        sample: &gst::Sample,
+        latency: gst::ClockTime,
+        pins: &Arc<AtomicUsize>,
+    ) -> Result<Self, Error> {
+        let reserved = pins
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pinned| {
+                (pinned < MAX_PINNED_FRAMES).then_some(pinned + 1)
+            })
+            .is_ok();
+        if reserved {
+            // released again if the frame can't be created
+            let pin = Pin(Arc::clone(pins));
+            let mut frame = Frame::new(sample, latency)?;
+            frame.pin = Some(pin);
+            return Ok(frame);
+        }
+
+        let buffer = sample.buffer().ok_or(Error::Caps)?.copy_deep()?;
+        let mut copy = sample.copy();
+        copy.get_mut().ok_or(Error::Caps)?.set_buffer(Some(&buffer));
+        Frame::new(&copy, latency)
+    }
+
+    /// Get the timing of the frame, if it has a timestamp.
+    pub(crate) fn info(&self) -> Option<FrameInfo> {
+        let pts = Duration::from_nanos(self.pts?.nseconds());
+        let position =
+            Duration::from_nanos(self.position.unwrap_or(gst::ClockTime::ZERO).nseconds());
+        Some(FrameInfo {
+            pts,
+            position,
+            index: time_to_frame(position, self.framerate),
+            duration: self
+                .duration
+                .map(|duration| Duration::from_nanos(duration.nseconds())),
+            keyframe: self.keyframe,
+        })
+    }
+
+    /// Get the format and colorimetry the frame was negotiated with.
+    pub(crate) fn video_info(&self) -> &gst_video::VideoInfo {
+        self.frame.info()
+    }
+
+    /// Get the raw data of a plane; the visible region starts at the plane's `offset`.
+    pub(crate) fn plane_data(&self, plane: usize) -> &[u8] {
+        self.frame.plane_data(plane as u32).unwrap_or(&[])
+    }
+
+    /// Get the samples of a single `(plane, component)` within the visible region.
+    pub(crate) fn channel(&self, (plane, component): (usize, usize)) -> Channel<'_> {
+        let layout = &self.planes[plane];
+        let depth = layout.depth as usize;
+        Channel {
+            data: self
+                .plane_data(plane)
+                .get(layout.offset + component * depth..)
+                .unwrap_or(&[]),
+            stride: layout.stride,
+            step: layout.components as usize * depth,
+            depth,
+            width: layout.width,
+            height: layout.height,
+            subsampling: layout.subsampling,
+        }
+    }
+}
+
+/// The samples of a single component of a frame, looked up once so that reading each sample is cheap.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Channel<'a> {
+    /// Plane data, starting at the component of the first visible sample.
+    data: &'a [u8],
+    stride: usize,
+    /// Bytes between horizontally adjacent samples.
+    step: usize,
+    depth: usize,
+    /// Size of the visible region in samples.
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) subsampling: (u32, u32),
+}
+
+impl Channel<'_> {
+    /// Reads the sample at `(x, y)` (in samples of the plane, not pixels), normalized to `0.0..=1.0`.
+    #[inline]
+    pub(crate) fn at(&self, x: u32, y: u32) -> f32 {
+        let i = y as usize * self.stride + x as usize * self.step;
+        match self.depth {
+            1 => self.data[i] as f32 / 255.0,
+            _ => u16::from_le_bytes([self.data[i], self.data[i + 1]]) as f32 / 65535.0,
+        }
+    }
+}
+
+/// Index of the frame displayed at `time`, given a framerate.
+///
+/// Timestamps are rounded to whole nanoseconds, so the start of a frame may be up to a nanosecond early;
+/// these still map to the frame they belong to.
+pub(crate) fn time_to_frame(time: Duration, framerate: gst::Fraction) -> u64 {
+    let (numer, denom) = (framerate.numer() as u128, framerate.denom() as u128);
+    if numer == 0 || denom == 0 {
+        return 0;
+    }
+    ((time.as_nanos() + 1) * numer / (denom * 1_000_000_000)) as u64
+}
+
+/// Time at which the frame at `index` starts, given a framerate.
+pub(crate) fn frame_to_time(index: u64, framerate: gst::Fraction) -> Duration {
+    let (numer, denom) = (framerate.numer() as u128, framerate.denom() as u128);
+    if numer == 0 || denom == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_nanos((index as u128 * denom * 1_000_000_000).div_ceil(numer) as u64)
+}
+
+/// A frame waiting in the [`FrameQueue`].
+struct Queued {
+    frame: Arc<Frame>,
+    epoch: u64,
+}
+
+/// Hands decoded frames from the worker thread to the renderer without either side ever blocking.
+///
+/// The worker queues frames slightly ahead of time, and the renderer takes the frame which is due
+/// whenever it draws, skipping any which are late.
+/// Frames are only ever removed by the renderer, which is what makes it safe to look at queued frames.
+#[derive(Debug)]
+pub(crate) struct FrameQueue {
+    slots: [AtomicPtr<Queued>; QUEUE_CAPACITY],
+    /// Incremented whenever the queue is cleared, so that frames from before then are discarded.
+    epoch: AtomicU64,
+}
+
+impl FrameQueue {
+    pub(crate) fn new() -> Self {
+        FrameQueue {
+            slots: std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the current epoch, which should be read before pulling the frame to push.
+    pub(crate) fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// Queues a new frame, dropping it if the queue is full (i.e., the renderer isn't drawing).
+    pub(crate) fn push(&self, frame: Arc<Frame>, epoch: u64) {
+        let queued = Box::into_raw(Box::new(Queued { frame, epoch }));
+        let queued_in = self.slots.iter().any(|slot| {
+            slot.compare_exchange(ptr::null_mut(), queued, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        });
+        if !queued_in {
+            // SAFETY: the pointer came from `Box::into_raw` above and was never shared
+            drop(unsafe { Box::from_raw(queued) });
+        }
+    }
+
+    /// Takes the latest frame which is due at the running time `now`, dropping any older frames.
+    /// Without a running time (e.g., while paused), the latest frame is taken regardless.
+    ///
+    /// Must only be called from one thread at a time.
+    pub(crate) fn take(&self, now: Option<gst::ClockTime>) -> Option<Arc<Frame>> {
+        let epoch = self.epoch();
+        let mut latest: Option<(usize, Option<gst::ClockTime>)> = None;
+        for (i, slot) in self.slots.iter().enumerate() {
+            let queued = slot.load(Ordering::Acquire);
+            if queued.is_null() {
+                continue;
+            }
+            // SAFETY: only the consumer removes frames, so it stays alive while it is in the slot
+            let queued = unsafe { &*queued };
+            if queued.epoch != epoch {
+                self.remove(i);
+                continue;
+            }
+            let due = queued.frame.due;
+            let is_due = match (now, due) {
+                (Some(now), Some(due)) => due <= now,
+                _ => true,
+            };
+            if is_due && latest.is_none_or(|(_, latest)| due >= latest) {
+                latest = Some((i, due));
+            }
+        }
+
+        let (index, due) = latest?;
+        let frame = self.remove(index);
+        // frames which were due before the taken one are late, so they will never be displayed
+        for (i, slot) in self.slots.iter().enumerate() {
+            let queued = slot.load(Ordering::Acquire);
+            // SAFETY: as above
+            if !queued.is_null() && unsafe { &*queued }.frame.due <= due {
+                self.remove(i);
+            }
+        }
+        frame
+    }
+
+    /// Drops all queued frames, including any the worker is about to push.
+    ///
+    /// Must only be called from one thread at a time.
+    pub(crate) fn clear(&self) {
+        self.epoch.fetch_add(1, Ordering::AcqRel);
+        for i in 0..QUEUE_CAPACITY {
+            self.remove(i);
+        }
+    }
+
+    fn remove(&self, index: usize) -> Option<Arc<Frame>> {
+        let queued = self.slots[index].swap(ptr::null_mut(), Ordering::AcqRel);
+        // SAFETY: the pointer came from `Box::into_raw` in `push`, and swapping it out transferred ownership
+        (!queued.is_null()).then(|| unsafe { Box::from_raw(queued) }.frame)
+    }
+}
+
+impl Drop for FrameQueue {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntsc() -> gst::Fraction {
+        gst::Fraction::new(30000, 1001)
+    }
+
+    #[test]
+    fn frame_starts_map_to_their_index() {
+        for framerate in [
+            ntsc(),
+            gst::Fraction::new(25, 1),
+            gst::Fraction::new(24000, 1001),
+        ] {
+            for index in [0, 1, 2, 29, 30, 1799, 107_892] {
+                let start = frame_to_time(index, framerate);
+                assert_eq!(time_to_frame(start, framerate), index, "{framerate}");
+                // timestamps truncated to the nanosecond still belong to the frame
+                if index > 0 {
+                    let early = start - Duration::from_nanos(1);
+                    assert_eq!(time_to_frame(early, framerate), index, "{framerate}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn time_within_frame() {
+        // frame 1 at 29.97 fps lasts from 33.366667ms to 66.733334ms
+        assert_eq!(time_to_frame(Duration::from_millis(34), ntsc()), 1);
+        assert_eq!(time_to_frame(Duration::from_millis(66), ntsc()), 1);
+        assert_eq!(time_to_frame(Duration::from_millis(67), ntsc()), 2);
+        assert_eq!(time_to_frame(Duration::from_secs(60), ntsc()), 1798);
+    }
+
+    #[test]
+    fn variable_framerate() {
+        let variable = gst::Fraction::new(0, 1);
+        assert_eq!(time_to_frame(Duration::from_secs(5), variable), 0);
+        assert_eq!(frame_to_time(5, variable), Duration::ZERO);
+    }
+
+    /// A tiny frame shown at `pts` milliseconds, with running time matching the timestamp.
+    fn frame(pts: u64) -> Arc<Frame> {
+        Arc::new(Frame::new(&sample(pts), gst::ClockTime::ZERO).unwrap())
+    }
+
+    fn sample(pts: u64) -> gst::Sample {
+        gst::init().unwrap();
+        let info = gst_video::VideoInfo::builder(gst_video::VideoFormat::Rgba, 2, 2)
+            .build()
+            .unwrap();
+        let mut buffer = gst::Buffer::with_size(info.size()).unwrap();
+        buffer
+            .get_mut()
+            .unwrap()
+            .set_pts(gst::ClockTime::from_mseconds(pts));
+        gst::Sample::builder()
+            .buffer(&buffer)
+            .caps(&info.to_caps().unwrap())
+            .segment(&gst::FormattedSegment::<gst::ClockTime>::new())
+            .build()
+    }
+
+    fn pts(frame: Option<Arc<Frame>>) -> Option<u64> {
+        frame?.pts.map(gst::ClockTime::mseconds)
+    }
+
+    #[test]
+    fn takes_latest_due_frame() {
+        let queue = FrameQueue::new();
+        for time in [0, 40, 80, 120] {
+            queue.push(frame(time), queue.epoch());
+        }
+        assert_eq!(
+            pts(queue.take(Some(gst::ClockTime::from_mseconds(90)))),
+            Some(80)
+        );
+        // the late frames were dropped, and the next one isn't due yet
+        assert_eq!(
+            pts(queue.take(Some(gst::ClockTime::from_mseconds(100)))),
+            None
+        );
+        assert_eq!(
+            pts(queue.take(Some(gst::ClockTime::from_mseconds(120)))),
+            Some(120)
+        );
+        assert_eq!(pts(queue.take(None)), None);
+    }
+
+    #[test]
+    fn takes_latest_frame_without_clock() {
+        let queue = FrameQueue::new();
+        for time in [0, 40, 80] {
+            queue.push(frame(time), queue.epoch());
+        }
+        assert_eq!(pts(queue.take(None)), Some(80));
+        assert_eq!(pts(queue.take(None)), None);
+    }
+
+    #[test]
+    fn drops_frames_when_full() {
+        let queue = FrameQueue::new();
+        for i in 0..QUEUE_CAPACITY as u64 + 1 {
+            queue.push(frame(i * 40), queue.epoch());
+        }
+        assert_eq!(
+            pts(queue.take(None)),
+            Some((QUEUE_CAPACITY as u64 - 1) * 40)
+        );
+    }
+
+    #[test]
+    fn clear_discards_stale_frames() {
+        let queue = FrameQueue::new();
+        let epoch = queue.epoch();
+        queue.push(frame(0), epoch);
+        queue.clear();
+        // pulled before the clear, so pushed with the old epoch
+        queue.push(frame(40), epoch);
+        assert_eq!(pts(queue.take(None)), None);
+
+        queue.push(frame(80), queue.epoch());
+        assert_eq!(pts(queue.take(None)), Some(80));
+    }
+
+    #[test]
+    fn copies_frames_beyond_pin_limit() {
+        let pins = Arc::new(AtomicUsize::new(0));
+        let sample = sample(40);
+        let pinned: Vec<_> = (0..MAX_PINNED_FRAMES)
+            .map(|_| Frame::pinned(&sample, gst::ClockTime::ZERO, &pins).unwrap())
+            .collect();
+        assert!(pinned.iter().all(|frame| frame.pin.is_some()));
+
+        let copied = Frame::pinned(&sample, gst::ClockTime::ZERO, &pins).unwrap();
+        assert!(copied.pin.is_none());
+        assert_eq!(copied.pts, Some(gst::ClockTime::from_mseconds(40)));
+        assert_eq!(pins.load(Ordering::Acquire), MAX_PINNED_FRAMES);
+
+        drop(pinned);
+        assert_eq!(pins.load(Ordering::Acquire), 0);
+    }
+}
@@ -34,15 +34,46 @@
 //!
 //! You can programmatically control the video (e.g., seek, pause, loop, grab thumbnails) by accessing various methods on [`Video`].
 
+mod builder;
+mod chapter;
+mod color;
+mod convert;
+mod event;
+mod format;
+mod frame;
+mod loader;
+mod metadata;
 mod pipeline;
+mod playlist;
+mod plugin;
+mod snapshot;
+mod tap;
+mod thumbnail;
+mod track;
+mod trickplay;
 mod video;
 mod video_player;
 
 use gstreamer as gst;
 use thiserror::Error;
 
-pub use video::Position;
+pub use builder::VideoBuilder;
+pub use chapter::Chapter;
+pub use color::ToneMapping;
+pub use convert::{Filter, Resize};
+pub use event::VideoEvent;
+pub use frame::FrameInfo;
+pub use loader::{LoadHandle, VideoLoader};
+pub use metadata::Metadata;
+pub use playlist::Playlist;
+pub use plugin::{MissingPlugin, MissingPluginKind};
+pub use snapshot::{ImageFormat, Snapshot};
+pub use tap::{DecodedFrame, FramePlane, FrameTap, MAX_TAP_CAPACITY};
+pub use thumbnail::Thumbnails;
+pub use track::{Track, TrackKind};
+pub use trickplay::{SpriteSheet, Trickplay};
 pub use video::Video;
+pub use video::{LoopMode, Position};
 pub use video_player::VideoPlayer;
 
 #[derive(Debug, Error)]
@@ -73,4 +104,73 @@ pub enum Error {
     Lock,
     #[error("invalid framerate: {0}")]
     Framerate(f64),
+    #[error("invalid {0:?} track index: {1}")]
+    Track(TrackKind, i32),
+    #[error("invalid chapter index: {0}")]
+    Chapter(usize),
+    #[error("failed to step frames")]
+    Step,
+    #[error("playlist is empty")]
+    EmptyPlaylist,
+    #[error("invalid playlist index: {0}")]
+    PlaylistIndex(usize),
+    #[error("loading was cancelled")]
+    Cancelled,
+    #[error("operation is not supported on live sources")]
+    Live,
+    #[error("media does not support seeking")]
+    NotSeekable,
+    #[error("loop region must end after it starts, within the media")]
+    LoopRegion,
+    #[error("timed out")]
+    Timeout,
+    #[error("interval must not be zero")]
+    Interval,
+    #[error("position is past the end of the media")]
+    EndOfStream,
+    #[error("no frame has been decoded yet")]
+    NoFrame,
+    #[error("failed to encode image")]
+    Encode,
+    #[error(
+        "missing GStreamer plugins: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    MissingPlugins(Vec<MissingPlugin>),
+}
+
+// `std::io::Error` isn't `Clone`, so it is recreated from its kind and message
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        match self {
+            Error::Glib(err) => Error::Glib(err.clone()),
+            Error::Bool(err) => Error::Bool(err.clone()),
+            Error::Bus => Error::Bus,
+            Error::AppSink(name) => Error::AppSink(name.clone()),
+            Error::StateChange(err) => Error::StateChange(*err),
+            Error::Cast => Error::Cast,
+            Error::Io(err) => Error::Io(std::io::Error::new(err.kind(), err.to_string())),
+            Error::Uri => Error::Uri,
+            Error::Caps => Error::Caps,
+            Error::Duration => Error::Duration,
+            Error::Sync => Error::Sync,
+            Error::Lock => Error::Lock,
+            Error::Framerate(framerate) => Error::Framerate(*framerate),
+            Error::Track(kind, index) => Error::Track(*kind, *index),
+            Error::Chapter(index) => Error::Chapter(*index),
+            Error::Step => Error::Step,
+            Error::EmptyPlaylist => Error::EmptyPlaylist,
+            Error::PlaylistIndex(index) => Error::PlaylistIndex(*index),
+            Error::Cancelled => Error::Cancelled,
+            Error::Live => Error::Live,
+            Error::NotSeekable => Error::NotSeekable,
+            Error::LoopRegion => Error::LoopRegion,
+            Error::Timeout => Error::Timeout,
+            Error::Interval => Error::Interval,
+            Error::EndOfStream => Error::EndOfStream,
+            Error::NoFrame => Error::NoFrame,
+            Error::Encode => Error::Encode,
+            Error::MissingPlugins(plugins) => Error::MissingPlugins(plugins.clone()),
+        }
+    }
 }
@@ -0,0 +1,132 @@
+use crate::video::lock;
+use crate::{Error, Video};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use iced::futures::channel::oneshot;
+use iced::futures::FutureExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Handle to cancel a [`VideoLoader`] from elsewhere (e.g., after the future was handed to an iced `Task`).
+#[derive(Debug, Clone)]
+pub struct LoadHandle {
+    /// The pipeline being loaded, until it is handed over to the video.
+    pipeline: Arc<Mutex<Option<gst::Pipeline>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl LoadHandle {
+    /// Stops loading the video; the loader will resolve to [`Error::Cancelled`].
+    /// Does nothing once the video has been loaded.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(pipeline) = &*lock(&self.pipeline) {
+            // wakes up the loading thread if it is waiting on the pipeline to preroll
+            if let Err(err) = pipeline.set_state(gst::State::Null) {
+                log::error!("failed to stop cancelled video: {err}");
+            }
+        }
+    }
+
+    /// Get if loading was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// A [`Video`] loading on a background thread, created by [`Video::new_async`].
+///
+/// This is a future which resolves once the video is ready to play, so it can be given to `iced::Task::perform`.
+/// Dropping it before it resolves cancels loading.
+pub struct VideoLoader {
+    receiver: oneshot::Receiver<Result<Video, Error>>,
+    handle: LoadHandle,
+    done: bool,
+}
+
+impl VideoLoader {
+    /// Runs `load` on a background thread; `pipeline` is stopped if loading is cancelled.
+    pub(crate) fn spawn<F>(pipeline: gst::Pipeline, load: F) -> Self
+    where
+        F: FnOnce() -> Result<Video, Error> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let handle = LoadHandle {
+            pipeline: Arc::new(Mutex::new(Some(pipeline))),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+
+        let pipeline = Arc::clone(&handle.pipeline);
+        let cancelled = Arc::clone(&handle.cancelled);
+        std::thread::spawn(move || {
+            let result = if cancelled.load(Ordering::SeqCst) {
+                Err(Error::Cancelled)
+            } else {
+                load()
+            };
+            // the video owns the pipeline from now on, so cancelling must no longer stop it
+            lock(&pipeline).take();
+            // cancelling may have happened after prerolling finished
+            let result = if cancelled.load(Ordering::SeqCst) {
+                Err(Error::Cancelled)
+            } else {
+                result
+            };
+            let _ = sender.send(result);
+        });
+
+        VideoLoader {
+            receiver,
+            handle,
+            done: false,
+        }
+    }
+
+    pub(crate) fn failed(err: Error) -> Self {
+        let (sender, receiver) = oneshot::channel();
+        let _ = sender.send(Err(err));
+        VideoLoader {
+            receiver,
+            handle: LoadHandle {
+                pipeline: Arc::new(Mutex::new(None)),
+                cancelled: Arc::new(AtomicBool::new(false)),
+            },
+            done: false,
+        }
+    }
+
+    /// Get a handle which can cancel loading.
+    pub fn handle(&self) -> LoadHandle {
+        self.handle.clone()
+    }
+
+    /// Stops loading the video; this will resolve to [`Error::Cancelled`].
+    pub fn cancel(&self) {
+        self.handle.cancel();
+    }
+}
+
+impl Future for VideoLoader {
+    type Output = Result<Video, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.receiver.poll_unpin(cx) {
+            Poll::Ready(result) => {
+                self.done = true;
+                Poll::Ready(result.unwrap_or(Err(Error::Cancelled)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for VideoLoader {
+    fn drop(&mut self) {
+        if !self.done {
+            self.handle.cancel();
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use gstreamer as gst;
+use iced::widget::image as img;
+
+/// Information about the media, gathered from the tags reported by GStreamer.
+///
+/// Tags may arrive at any point during playback (e.g., when a new stream starts),
+/// so this is updated as the video plays.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    /// Title of the media.
+    pub title: Option<String>,
+    /// Artist (or author) of the media.
+    pub artist: Option<String>,
+    /// Album (or collection) the media belongs to.
+    pub album: Option<String>,
+    /// Name of the container format (e.g., "Matroska").
+    pub container: Option<String>,
+    /// Name of the video codec.
+    pub video_codec: Option<String>,
+    /// Name of the audio codec.
+    pub audio_codec: Option<String>,
+    /// Name of the subtitle codec.
+    pub subtitle_codec: Option<String>,
+    /// Exact or average bitrate in bits per second.
+    pub bitrate: Option<u32>,
+    /// Nominal bitrate in bits per second.
+    pub nominal_bitrate: Option<u32>,
+    /// Minimum bitrate in bits per second.
+    pub minimum_bitrate: Option<u32>,
+    /// Maximum bitrate in bits per second.
+    pub maximum_bitrate: Option<u32>,
+    /// Language of the media (usually an ISO-639 code).
+    pub language: Option<String>,
+    /// Date and time the media was created.
+    pub creation_date: Option<gst::DateTime>,
+    /// Embedded cover art (or preview image if there is no cover art).
+    pub cover_art: Option<img::Handle>,
+    /// All tags received so far, including those not covered by the fields above.
+    pub tags: gst::TagList,
+}
+
+impl Metadata {
+    /// Merges newly received tags, replacing any previous values.
+    pub(crate) fn merge(&mut self, new: &gst::TagList) {
+        self.tags = self.tags.merge(new, gst::TagMergeMode::Replace);
+
+        let tags = &self.tags;
+        self.title = tag_string::<gst::tags::Title>(tags);
+        self.artist = tag_string::<gst::tags::Artist>(tags);
+        self.album = tag_string::<gst::tags::Album>(tags);
+        self.container = tag_string::<gst::tags::ContainerFormat>(tags);
+        self.video_codec = tag_string::<gst::tags::VideoCodec>(tags);
+        self.audio_codec = tag_string::<gst::tags::AudioCodec>(tags);
+        self.subtitle_codec = tag_string::<gst::tags::SubtitleCodec>(tags);
+        self.bitrate = tags.get::<gst::tags::Bitrate>().map(|v| v.get());
+        self.nominal_bitrate = tags.get::<gst::tags::NominalBitrate>().map(|v| v.get());
+        self.minimum_bitrate = tags.get::<gst::tags::MinimumBitrate>().map(|v| v.get());
+        self.maximum_bitrate = tags.get::<gst::tags::MaximumBitrate>().map(|v| v.get());
+        self.language = tag_string::<gst::tags::LanguageCode>(tags)
+            .or_else(|| tag_string::<gst::tags::LanguageName>(tags));
+        self.creation_date = tags.get::<gst::tags::DateTime>().map(|v| v.get());
+
+        // only decode the cover art when a new image arrives
+        let image = new
+            .get::<gst::tags::Image>()
+            .or_else(|| new.get::<gst::tags::PreviewImage>());
+        if let Some(image) = image {
+            let sample = image.get();
+            self.cover_art = sample.buffer().and_then(|buffer| {
+                let map = buffer.map_readable().ok()?;
+                Some(img::Handle::from_bytes(map.as_slice().to_vec()))
+            });
+        }
+    }
+}
+
+/// Reads a string tag as an owned `String`.
+pub(crate) fn tag_string<T>(tags: &gst::TagListRef) -> Option<String>
+where
+    T: for<'a> gst::tags::Tag<'a, TagType = &'a str>,
+{
+    let value = tags.get::<T>()?;
+    let value: &str = value.get();
+    Some(value.to_owned())
+}
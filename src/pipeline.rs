@@ -1,32 +1,134 @@
+use crate::color::{ColorSpace, ToneMapping, Transfer};
+use crate::format::{PixelFormat, Plane};
+use crate::frame::Frame;
 use iced_wgpu::primitive::Primitive;
 use iced_wgpu::wgpu;
 use std::{
     collections::BTreeMap,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc,
     },
 };
 
 #[repr(C)]
 struct Uniforms {
     rect: [f32; 4],
+    /// Columns of the YUV to RGB matrix, padded to 16 bytes as WGSL requires.
+    yuv_matrix: [[f32; 4]; 3],
+    /// Columns of the gamut conversion matrix, padded to 16 bytes.
+    gamut: [[f32; 4]; 3],
+    yuv_offset: [f32; 4],
+    /// Luma coefficients of the source, for HLG and tone mapping.
+    luma: [f32; 4],
+    /// 0 = sRGB, 1 = power curve with exponent `gamma`, 2 = PQ, 3 = HLG.
+    transfer: u32,
+    gamma: f32,
+    /// Brightest luminance in the content relative to SDR white.
+    peak: f32,
+    /// 0 = none, 1 = Reinhard, 2 = Hable, 3 = BT.2390.
+    tone_mapping: u32,
+}
+
+/// Transposes a row-major matrix into padded columns for WGSL.
+fn columns(rows: [[f32; 3]; 3]) -> [[f32; 4]; 3] {
+    std::array::from_fn(|col| [rows[0][col], rows[1][col], rows[2][col], 0.0])
+}
+
+/// GPU resources of a single video.
+struct VideoTextures {
+    /// One texture per plane of the frame.
+    planes: Vec<wgpu::Texture>,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    alive: Arc<AtomicBool>,
+    format: PixelFormat,
+    color: ColorSpace,
+    size: (u32, u32),
+}
+
+impl VideoTextures {
+    fn destroy(&self) {
+        for texture in &self.planes {
+            texture.destroy();
+        }
+        self.buffer.destroy();
+    }
 }
 
 struct VideoPipeline {
-    pipeline: wgpu::RenderPipeline,
+    /// One pipeline per pixel format, each sampling the planes differently.
+    pipelines: BTreeMap<PixelFormat, wgpu::RenderPipeline>,
+    /// If the surface can show values brighter than SDR white, so HDR can be passed through.
+    hdr_output: bool,
     bg0_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
-    textures: BTreeMap<
-        u64,
-        (
-            wgpu::Texture,
-            wgpu::Texture,
-            wgpu::Buffer,
-            wgpu::BindGroup,
-            Arc<AtomicBool>,
-        ),
-    >,
+    /// Bound in place of planes which the pixel format doesn't have.
+    placeholder_float: wgpu::TextureView,
+    placeholder_uint: wgpu::TextureView,
+    textures: BTreeMap<u64, VideoTextures>,
+}
+
+/// Bindings of the (up to three) planes of 8-bit formats.
+const FLOAT_PLANE_BINDINGS: [u32; 3] = [0, 1, 4];
+/// Bindings of the planes of 16-bit formats, which can't be filtered and so are stored as integers.
+const UINT_PLANE_BINDINGS: [u32; 2] = [5, 6];
+
+/// Texture format able to hold a plane without conversion.
+fn plane_texture_format(plane: &Plane) -> wgpu::TextureFormat {
+    match (plane.components, plane.depth) {
+        (1, 1) => wgpu::TextureFormat::R8Unorm,
+        (2, 1) => wgpu::TextureFormat::Rg8Unorm,
+        (1, _) => wgpu::TextureFormat::R16Uint,
+        (2, _) => wgpu::TextureFormat::Rg16Uint,
+        _ => wgpu::TextureFormat::Rgba8Unorm,
+    }
+}
+
+/// Fragment shader entry point which converts a pixel format to RGB.
+fn entry_point(format: PixelFormat) -> &'static str {
+    match format {
+        PixelFormat::Nv12 => "fs_nv12",
+        PixelFormat::I420 | PixelFormat::Y444 => "fs_planar",
+        PixelFormat::P010 | PixelFormat::P016 => "fs_yuv16",
+        PixelFormat::Rgba => "fs_rgba",
+        PixelFormat::Bgra => "fs_bgra",
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("iced_video_player texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn create_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("iced_video_player texture view"),
+        format: None,
+        dimension: None,
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: 0,
+        array_layer_count: None,
+    })
 }
 
 impl VideoPipeline {
@@ -67,7 +169,7 @@ impl VideoPipeline {
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 3,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -75,6 +177,36 @@ impl VideoPipeline {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -84,32 +216,38 @@ impl VideoPipeline {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("iced_video_player pipeline"),
-            layout: Some(&layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-        });
+        let pipelines = PixelFormat::ALL
+            .into_iter()
+            .map(|pixel_format| {
+                let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("iced_video_player pipeline"),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: entry_point(pixel_format),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview: None,
+                });
+                (pixel_format, pipeline)
+            })
+            .collect();
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("iced_video_player sampler"),
@@ -126,10 +264,21 @@ impl VideoPipeline {
             border_color: None,
         });
 
+        let placeholder_float =
+            create_view(&create_texture(device, 1, 1, wgpu::TextureFormat::R8Unorm));
+        let placeholder_uint =
+            create_view(&create_texture(device, 1, 1, wgpu::TextureFormat::R16Uint));
+
         VideoPipeline {
-            pipeline,
+            pipelines,
+            hdr_output: matches!(
+                format,
+                wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float
+            ),
             bg0_layout,
             sampler,
+            placeholder_float,
+            placeholder_uint,
             textures: BTreeMap::new(),
         }
     }
@@ -140,61 +289,34 @@ impl VideoPipeline {
         queue: &wgpu::Queue,
         video_id: u64,
         alive: &Arc<AtomicBool>,
-        (width, height): (u32, u32),
-        frame: &[u8],
+        frame: &Frame,
     ) {
-        if !self.textures.contains_key(&video_id) {
-            let texture_y = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("iced_video_player texture"),
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::R8Unorm,
-                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
+        let size = (frame.width, frame.height);
 
-            let texture_uv = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("iced_video_player texture"),
-                size: wgpu::Extent3d {
-                    width: width / 2,
-                    height: height / 2,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rg8Unorm,
-                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
-
-            let view_y = texture_y.create_view(&wgpu::TextureViewDescriptor {
-                label: Some("iced_video_player texture view"),
-                format: None,
-                dimension: None,
-                aspect: wgpu::TextureAspect::All,
-                base_mip_level: 0,
-                mip_level_count: None,
-                base_array_layer: 0,
-                array_layer_count: None,
-            });
+        // the format or resolution changed mid-stream, so the textures must be recreated
+        if let Some(textures) = self
+            .textures
+            .get(&video_id)
+            .filter(|textures| textures.format != frame.format || textures.size != size)
+        {
+            textures.destroy();
+            self.textures.remove(&video_id);
+        }
 
-            let view_uv = texture_uv.create_view(&wgpu::TextureViewDescriptor {
-                label: Some("iced_video_player texture view"),
-                format: None,
-                dimension: None,
-                aspect: wgpu::TextureAspect::All,
-                base_mip_level: 0,
-                mip_level_count: None,
-                base_array_layer: 0,
-                array_layer_count: None,
-            });
+        if !self.textures.contains_key(&video_id) {
+            let planes: Vec<_> = frame
+                .planes
+                .iter()
+                .map(|plane| {
+                    create_texture(
+                        device,
+                        plane.width,
+                        plane.height,
+                        plane_texture_format(plane),
+                    )
+                })
+                .collect();
+            let views: Vec<_> = planes.iter().map(create_view).collect();
 
             let buffer = device.create_buffer(&wgpu::BufferDescriptor {
                 label: Some("iced_video_player uniform buffer"),
@@ -203,99 +325,132 @@ impl VideoPipeline {
                 mapped_at_creation: false,
             });
 
+            let plane_bindings: &[u32] = if frame.planes.iter().any(|plane| plane.depth > 1) {
+                &UINT_PLANE_BINDINGS
+            } else {
+                &FLOAT_PLANE_BINDINGS
+            };
+            let plane_view = |binding: u32| {
+                plane_bindings
+                    .iter()
+                    .position(|&b| b == binding)
+                    .and_then(|i| views.get(i))
+            };
+
+            let mut entries = vec![
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+            ];
+            for binding in FLOAT_PLANE_BINDINGS {
+                entries.push(wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(
+                        plane_view(binding).unwrap_or(&self.placeholder_float),
+                    ),
+                });
+            }
+            for binding in UINT_PLANE_BINDINGS {
+                entries.push(wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(
+                        plane_view(binding).unwrap_or(&self.placeholder_uint),
+                    ),
+                });
+            }
+
             let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("iced_video_player bind group"),
                 layout: &self.bg0_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&view_y),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&view_uv),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                            buffer: &buffer,
-                            offset: 0,
-                            size: None,
-                        }),
-                    },
-                ],
+                entries: &entries,
             });
 
             self.textures.insert(
                 video_id,
-                (texture_y, texture_uv, buffer, bind_group, Arc::clone(alive)),
+                VideoTextures {
+                    planes,
+                    buffer,
+                    bind_group,
+                    alive: Arc::clone(alive),
+                    format: frame.format,
+                    color: frame.color,
+                    size,
+                },
             );
         }
 
-        let (texture_y, texture_uv, _, _, _) = self.textures.get(&video_id).unwrap();
-
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: texture_y,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &frame[..(width * height) as usize],
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(width),
-                rows_per_image: Some(height),
-            },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: texture_uv,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &frame[(width * height) as usize..],
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(width),
-                rows_per_image: Some(height / 2),
-            },
-            wgpu::Extent3d {
-                width: width / 2,
-                height: height / 2,
-                depth_or_array_layers: 1,
-            },
-        );
+        let textures = self.textures.get_mut(&video_id).unwrap();
+        textures.color = frame.color;
+
+        for (i, (texture, plane)) in textures.planes.iter().zip(&frame.planes).enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &frame.plane_data(i)[plane.offset..],
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(plane.stride as u32),
+                    rows_per_image: Some(plane.height),
+                },
+                wgpu::Extent3d {
+                    width: plane.width,
+                    height: plane.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
     }
 
     fn cleanup(&mut self) {
         let ids: Vec<_> = self
             .textures
             .iter()
-            .filter_map(|(id, (_, _, _, _, alive))| (!alive.load(Ordering::SeqCst)).then_some(*id))
+            .filter_map(|(id, textures)| (!textures.alive.load(Ordering::SeqCst)).then_some(*id))
             .collect();
         for id in ids {
-            if let Some((texture_y, texture_uv, buffer, _, _)) = self.textures.remove(&id) {
-                texture_y.destroy();
-                texture_uv.destroy();
-                buffer.destroy();
+            if let Some(textures) = self.textures.remove(&id) {
+                textures.destroy();
             }
         }
     }
 
-    fn prepare(&mut self, queue: &wgpu::Queue, video_id: u64, bounds: &iced::Rectangle) {
-        if let Some((_, _, buffer, _, _)) = self.textures.get(&video_id) {
+    fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        video_id: u64,
+        bounds: &iced::Rectangle,
+        tone_mapping: ToneMapping,
+    ) {
+        if let Some(VideoTextures { buffer, color, .. }) = self.textures.get(&video_id) {
+            let (transfer, gamma) = match color.transfer {
+                Transfer::Srgb => (0, 1.0),
+                Transfer::Gamma(gamma) => (1, gamma),
+                Transfer::Pq => (2, 1.0),
+                Transfer::Hlg => (3, 1.0),
+            };
+            let tone_mapping = if color.transfer.is_hdr() {
+                match tone_mapping.resolve(self.hdr_output) {
+                    ToneMapping::Passthrough => 0,
+                    ToneMapping::Reinhard => 1,
+                    ToneMapping::Hable => 2,
+                    ToneMapping::Bt2390 => 3,
+                }
+            } else {
+                0
+            };
             let uniforms = Uniforms {
                 rect: [
                     bounds.x,
@@ -303,6 +458,14 @@ impl VideoPipeline {
                     bounds.x + bounds.width,
                     bounds.y + bounds.height,
                 ],
+                yuv_matrix: columns(color.matrix),
+                gamut: columns(color.gamut),
+                yuv_offset: [color.offset[0], color.offset[1], color.offset[2], 0.0],
+                luma: [color.luma[0], color.luma[1], color.luma[2], 0.0],
+                transfer,
+                gamma,
+                peak: color.peak,
+                tone_mapping,
             };
             queue.write_buffer(buffer, 0, unsafe {
                 std::slice::from_raw_parts(
@@ -322,7 +485,10 @@ impl VideoPipeline {
         viewport: &iced::Rectangle<u32>,
         video_id: u64,
     ) {
-        if let Some((_, _, _, bind_group, _)) = self.textures.get(&video_id) {
+        if let Some(VideoTextures {
+            bind_group, format, ..
+        }) = self.textures.get(&video_id)
+        {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("iced_video_player render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -338,7 +504,7 @@ impl VideoPipeline {
                 occlusion_query_set: None,
             });
 
-            pass.set_pipeline(&self.pipeline);
+            pass.set_pipeline(&self.pipelines[format]);
             pass.set_bind_group(0, bind_group, &[]);
             pass.set_viewport(
                 viewport.x as _,
@@ -357,25 +523,23 @@ impl VideoPipeline {
 pub(crate) struct VideoPrimitive {
     video_id: u64,
     alive: Arc<AtomicBool>,
-    frame: Arc<Mutex<Vec<u8>>>,
-    size: (u32, u32),
-    upload_frame: bool,
+    /// New frame to upload, if any.
+    frame: Option<Arc<Frame>>,
+    tone_mapping: ToneMapping,
 }
 
 impl VideoPrimitive {
     pub fn new(
         video_id: u64,
         alive: Arc<AtomicBool>,
-        frame: Arc<Mutex<Vec<u8>>>,
-        size: (u32, u32),
-        upload_frame: bool,
+        frame: Option<Arc<Frame>>,
+        tone_mapping: ToneMapping,
     ) -> Self {
         VideoPrimitive {
             video_id,
             alive,
             frame,
-            size,
-            upload_frame,
+            tone_mapping,
         }
     }
 }
@@ -396,18 +560,11 @@ impl Primitive for VideoPrimitive {
 
         let pipeline = storage.get_mut::<VideoPipeline>().unwrap();
 
-        if self.upload_frame {
-            pipeline.upload(
-                device,
-                queue,
-                self.video_id,
-                &self.alive,
-                self.size,
-                self.frame.lock().expect("lock frame mutex").as_slice(),
-            );
+        if let Some(frame) = &self.frame {
+            pipeline.upload(device, queue, self.video_id, &self.alive, frame);
         }
 
-        pipeline.prepare(queue, self.video_id, bounds);
+        pipeline.prepare(queue, self.video_id, bounds, self.tone_mapping);
     }
 
     fn render(
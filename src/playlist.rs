@@ -0,0 +1,295 @@
+use crate::video::lock;
+use crate::{Error, Video};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use std::sync::{Arc, Mutex};
+
+struct State {
+    items: Vec<url::Url>,
+    /// Order in which the items are played, as indices into `items`.
+    order: Vec<usize>,
+    /// Position of the current item in `order`.
+    position: usize,
+    /// Position of the item queued for gapless playback in `order`, if any.
+    queued: Option<usize>,
+    shuffle: bool,
+    repeat: bool,
+}
+
+impl State {
+    fn next_position(&self) -> Option<usize> {
+        if self.position + 1 < self.order.len() {
+            Some(self.position + 1)
+        } else if self.repeat {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn previous_position(&self) -> Option<usize> {
+        if self.position > 0 {
+            Some(self.position - 1)
+        } else if self.repeat {
+            Some(self.order.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Queues the next item once playbin is about to finish the current one, returning its position.
+    ///
+    /// By then, playbin has switched over to the item queued before (if any), so it becomes the current one.
+    fn queue_next(&mut self) -> Option<usize> {
+        if let Some(queued) = self.queued.take() {
+            self.position = queued;
+        }
+        self.queued = self.next_position();
+        self.queued
+    }
+
+    fn url(&self, position: usize) -> &url::Url {
+        &self.items[self.order[position]]
+    }
+}
+
+/// A queue of media played one after the other in a single [`Video`].
+///
+/// Transitions between items are gapless; the next item is queued while the current one is still playing.
+/// Render it by passing [`Playlist::video`] to a [`VideoPlayer`](crate::VideoPlayer).
+pub struct Playlist {
+    video: Video,
+    state: Arc<Mutex<State>>,
+}
+
+impl Playlist {
+    /// Create a new playlist from a list of URIs, starting playback of the first.
+    pub fn new(items: Vec<url::Url>) -> Result<Self, Error> {
+        let first = items.first().ok_or(Error::EmptyPlaylist)?;
+        let video = Video::new(first)?;
+
+        let state = Arc::new(Mutex::new(State {
+            order: (0..items.len()).collect(),
+            items,
+            position: 0,
+            queued: None,
+            shuffle: false,
+            repeat: false,
+        }));
+
+        let state_ref = Arc::clone(&state);
+        video
+            .pipeline()
+            .connect("about-to-finish", false, move |args| {
+                let playbin = args.first()?.get::<gst::Element>().ok()?;
+                let mut state = lock(&state_ref);
+                if let Some(position) = state.queue_next() {
+                    playbin.set_property("uri", state.url(position).as_str());
+                }
+                None
+            });
+
+        Ok(Playlist { video, state })
+    }
+
+    /// Get the video playing the current item.
+    pub fn video(&self) -> &Video {
+        &self.video
+    }
+
+    /// Get the video playing the current item, e.g., to pause or seek.
+    pub fn video_mut(&mut self) -> &mut Video {
+        &mut self.video
+    }
+
+    /// Get the items in the playlist, in the order they were added.
+    pub fn items(&self) -> Vec<url::Url> {
+        lock(&self.state).items.clone()
+    }
+
+    /// Append an item to the end of the playlist.
+    pub fn push(&mut self, item: url::Url) {
+        let mut state = lock(&self.state);
+        state.items.push(item);
+        let index = state.items.len() - 1;
+        state.order.push(index);
+    }
+
+    /// Get the index (into [`Playlist::items`]) of the item currently playing.
+    pub fn current(&self) -> usize {
+        let mut state = lock(&self.state);
+
+        // the queued item only becomes current once playbin actually switches over to it
+        if let Some(queued) = state.queued {
+            let current_uri = self
+                .video
+                .pipeline()
+                .property::<Option<String>>("current-uri");
+            if current_uri.as_deref() == Some(state.url(queued).as_str()) {
+                state.position = queued;
+                state.queued = None;
+            }
+        }
+
+        state.order[state.position]
+    }
+
+    /// Skip to the next item.
+    /// Does nothing if this is the last item and repeat is disabled.
+    pub fn next_item(&mut self) -> Result<(), Error> {
+        self.current();
+        let position = lock(&self.state).next_position();
+        match position {
+            Some(position) => self.play_position(position),
+            None => Ok(()),
+        }
+    }
+
+    /// Go back to the previous item.
+    /// Restarts the current item if this is the first item and repeat is disabled.
+    pub fn previous_item(&mut self) -> Result<(), Error> {
+        self.current();
+        let position = lock(&self.state).previous_position();
+        match position {
+            Some(position) => self.play_position(position),
+            None => self.video.restart_stream(),
+        }
+    }
+
+    /// Jump to a given item (as an index into [`Playlist::items`]).
+    pub fn jump(&mut self, index: usize) -> Result<(), Error> {
+        let position = lock(&self.state)
+            .order
+            .iter()
+            .position(|&item| item == index)
+            .ok_or(Error::PlaylistIndex(index))?;
+        self.play_position(position)
+    }
+
+    /// Get if the items are played in a random order.
+    pub fn shuffle(&self) -> bool {
+        lock(&self.state).shuffle
+    }
+
+    /// Set if the items are played in a random order.
+    /// The current item keeps playing either way, as does an item already queued to play next.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        let current = self.current();
+        let mut state = lock(&self.state);
+        state.shuffle = shuffle;
+        let queued = state.queued.map(|position| state.order[position]);
+
+        let mut order: Vec<usize> = (0..state.items.len()).collect();
+        if shuffle {
+            // the current (and queued) items go first so that all the others are still to come
+            order.swap(0, current);
+            let mut start = 1;
+            if let Some(queued) = queued.filter(|&queued| queued != current) {
+                let position = order.iter().position(|&item| item == queued).unwrap_or(1);
+                order.swap(1, position);
+                start = 2;
+            }
+            shuffle_indices(&mut order[start..]);
+            state.position = 0;
+            state.queued = queued.map(|queued| if queued == current { 0 } else { 1 });
+        } else {
+            state.position = current;
+            state.queued = queued;
+        }
+        state.order = order;
+    }
+
+    /// Get if the playlist starts over once the last item ends.
+    pub fn repeat(&self) -> bool {
+        lock(&self.state).repeat
+    }
+
+    /// Set if the playlist starts over once the last item ends.
+    pub fn set_repeat(&mut self, repeat: bool) {
+        lock(&self.state).repeat = repeat;
+    }
+
+    fn play_position(&mut self, position: usize) -> Result<(), Error> {
+        let url = {
+            let mut state = lock(&self.state);
+            state.position = position;
+            state.queued = None;
+            state.url(position).clone()
+        };
+        self.video.0.get_mut().set_uri(&url)
+    }
+}
+
+/// Fisher-Yates shuffle, seeded from the standard library's random hasher keys.
+fn shuffle_indices(indices: &mut [usize]) {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut seed = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+        | 1;
+    for i in (1..indices.len()).rev() {
+        // xorshift64
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        indices.swap(i, (seed % (i as u64 + 1)) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(len: usize) -> State {
+        State {
+            items: (0..len)
+                .map(|i| url::Url::parse(&format!("file:///{i}.mp4")).unwrap())
+                .collect(),
+            order: (0..len).collect(),
+            position: 0,
+            queued: None,
+            shuffle: false,
+            repeat: false,
+        }
+    }
+
+    #[test]
+    fn gapless_without_current() {
+        // playbin asks for the next item near the end of each one, without the app ever calling `current`
+        let mut state = state(3);
+        assert_eq!(state.queue_next(), Some(1));
+        assert_eq!(state.queue_next(), Some(2));
+        assert_eq!(state.position, 1);
+        assert_eq!(state.queue_next(), None);
+        assert_eq!(state.position, 2);
+    }
+
+    #[test]
+    fn gapless_repeat() {
+        let mut state = state(3);
+        state.repeat = true;
+        let queued: Vec<_> = (0..5).filter_map(|_| state.queue_next()).collect();
+        assert_eq!(queued, [1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn shuffle_is_permutation() {
+        for len in [0, 1, 2, 10, 100] {
+            let mut indices: Vec<usize> = (0..len).collect();
+            shuffle_indices(&mut indices);
+            let mut sorted = indices.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..len).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn shuffle_moves_items() {
+        // the chance of 100 items staying in order is negligible
+        let ordered: Vec<usize> = (0..100).collect();
+        let mut indices = ordered.clone();
+        shuffle_indices(&mut indices);
+        assert_ne!(indices, ordered);
+    }
+}
@@ -0,0 +1,76 @@
+use gstreamer as gst;
+
+/// What kind of GStreamer plugin is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MissingPluginKind {
+    /// A decoder for a media format.
+    Decoder,
+    /// An encoder for a media format.
+    Encoder,
+    /// A specific element.
+    Element,
+    /// A source for a URI protocol (e.g., `rtsp`).
+    UriSource,
+    /// A sink for a URI protocol.
+    UriSink,
+}
+
+/// A GStreamer plugin needed to play the media which is not installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingPlugin {
+    /// What kind of plugin is missing.
+    pub kind: MissingPluginKind,
+    /// The caps (for decoders and encoders), element name, or URI protocol which is needed.
+    pub detail: String,
+    /// Human-readable description of what is missing (e.g., "H.265 (Main Profile) decoder").
+    pub description: String,
+}
+
+impl MissingPlugin {
+    /// Parses a `missing-plugin` element message, as posted by `playbin` and `decodebin`.
+    pub(crate) fn from_message(msg: &gst::MessageRef) -> Option<Self> {
+        let gst::MessageView::Element(element) = msg.view() else {
+            return None;
+        };
+        let s = element.structure()?;
+        if !s.has_name("missing-plugin") {
+            return None;
+        }
+
+        let kind = match s.get::<&str>("type").ok()? {
+            "decoder" => MissingPluginKind::Decoder,
+            "encoder" => MissingPluginKind::Encoder,
+            "element" => MissingPluginKind::Element,
+            "urisource" => MissingPluginKind::UriSource,
+            "urisink" => MissingPluginKind::UriSink,
+            _ => return None,
+        };
+        // decoders and encoders are described by caps, everything else by a string
+        let detail = match s.get::<gst::Caps>("detail") {
+            Ok(caps) => caps.to_string(),
+            Err(_) => s.get::<String>("detail").ok()?,
+        };
+        let description = s.get::<String>("name").unwrap_or_else(|_| {
+            let what = detail.split(',').next().unwrap_or(&detail);
+            match kind {
+                MissingPluginKind::Decoder => format!("{what} decoder"),
+                MissingPluginKind::Encoder => format!("{what} encoder"),
+                MissingPluginKind::Element => format!("GStreamer element {what}"),
+                MissingPluginKind::UriSource => format!("{what} protocol source"),
+                MissingPluginKind::UriSink => format!("{what} protocol sink"),
+            }
+        });
+
+        Some(MissingPlugin {
+            kind,
+            detail,
+            description,
+        })
+    }
+}
+
+impl std::fmt::Display for MissingPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.description, self.detail)
+    }
+}
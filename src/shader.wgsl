@@ -5,6 +5,17 @@ struct VertexOutput {
 
 struct Uniforms {
     rect: vec4<f32>,
+    yuv_matrix: mat3x3<f32>,
+    gamut: mat3x3<f32>,
+    yuv_offset: vec4<f32>,
+    luma: vec4<f32>,
+    // 0 = sRGB, 1 = power curve, 2 = PQ, 3 = HLG
+    transfer: u32,
+    gamma: f32,
+    // brightest luminance in the content, relative to SDR white
+    peak: f32,
+    // 0 = none, 1 = Reinhard, 2 = Hable, 3 = BT.2390
+    tone_mapping: u32,
 }
 
 @group(0) @binding(0)
@@ -19,6 +30,16 @@ var s: sampler;
 @group(0) @binding(3)
 var<uniform> uniforms: Uniforms;
 
+@group(0) @binding(4)
+var tex_v: texture_2d<f32>;
+
+// 16-bit formats aren't filterable, so they are sampled manually
+@group(0) @binding(5)
+var tex_y16: texture_2d<u32>;
+
+@group(0) @binding(6)
+var tex_uv16: texture_2d<u32>;
+
 @vertex
 fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
     let quad = array<vec2<f32>, 6>(
@@ -38,26 +59,166 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
     return out;
 }
 
+// luminance of SDR reference white in nits (BT.2408)
+const SDR_WHITE: f32 = 203.0;
+
+const PQ_M1: f32 = 0.1593017578125;
+const PQ_M2: f32 = 78.84375;
+const PQ_C1: f32 = 0.8359375;
+const PQ_C2: f32 = 18.8515625;
+const PQ_C3: f32 = 18.6875;
+
+fn pq_to_nits(e: vec3<f32>) -> vec3<f32> {
+    let p = pow(max(e, vec3<f32>(0.0)), vec3<f32>(1.0 / PQ_M2));
+    return 10000.0 * pow(max(p - PQ_C1, vec3<f32>(0.0)) / (PQ_C2 - PQ_C3 * p), vec3<f32>(1.0 / PQ_M1));
+}
+
+fn nits_to_pq(nits: f32) -> f32 {
+    let y = pow(max(nits / 10000.0, 0.0), PQ_M1);
+    return pow((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y), PQ_M2);
+}
+
+fn hlg_to_linear(e: vec3<f32>) -> vec3<f32> {
+    // inverse OETF to scene light, then the OOTF for a 1000 nit display (system gamma 1.2)
+    let lo = e * e / 3.0;
+    let hi = (exp((e - 0.55991073) / 0.17883277) + 0.28466892) / 12.0;
+    let scene = select(hi, lo, e <= vec3<f32>(0.5));
+    let y = max(dot(uniforms.luma.xyz, scene), 0.0);
+    return scene * (1000.0 * pow(y, 0.2) / SDR_WHITE);
+}
+
+fn hable(x: f32) -> f32 {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f;
+}
+
+// maps luminance in 0..peak (relative to SDR white) into 0..1
+fn tone_map(l: f32) -> f32 {
+    let peak = uniforms.peak;
+    if peak <= 1.0 {
+        return l;
+    }
+
+    switch uniforms.tone_mapping {
+        case 1u: {
+            return l * (1.0 + l / (peak * peak)) / (1.0 + l);
+        }
+        case 2u: {
+            return hable(l) / hable(peak);
+        }
+        case 3u: {
+            // BT.2390 EETF, in the PQ domain normalized to the source peak
+            let source_peak = nits_to_pq(peak * SDR_WHITE);
+            let e1 = nits_to_pq(l * SDR_WHITE) / source_peak;
+            let max_lum = nits_to_pq(SDR_WHITE) / source_peak;
+            let knee = 1.5 * max_lum - 0.5;
+            var e2 = e1;
+            if e1 >= knee {
+                let t = (e1 - knee) / (1.0 - knee);
+                let t2 = t * t;
+                let t3 = t2 * t;
+                e2 = (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+                    + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+                    + (-2.0 * t3 + 3.0 * t2) * max_lum;
+            }
+            return pq_to_nits(vec3<f32>(e2 * source_peak)).x / SDR_WHITE;
+        }
+        default: {
+            return l;
+        }
+    }
+}
+
+// decodes non-linear RGB to linear light (1.0 = SDR white), tone maps HDR, then converts to BT.709 primaries
+fn to_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let c = clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    var linear = vec3<f32>(0.0);
+    switch uniforms.transfer {
+        case 0u: {
+            let threshold = c <= vec3<f32>(0.04045);
+            let hi = pow((c + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+            let lo = c * vec3<f32>(1.0 / 12.92);
+            linear = select(hi, lo, threshold);
+        }
+        case 1u: {
+            linear = pow(c, vec3<f32>(uniforms.gamma));
+        }
+        case 2u: {
+            linear = pq_to_nits(c) / SDR_WHITE;
+        }
+        default: {
+            linear = hlg_to_linear(c);
+        }
+    }
+
+    if uniforms.tone_mapping != 0u {
+        // scale by luminance so that hues are preserved
+        let l = dot(uniforms.luma.xyz, linear);
+        if l > 0.0 {
+            linear = linear * (tone_map(l) / l);
+        }
+    }
+
+    return uniforms.gamut * linear;
+}
+
+fn yuv_to_rgb(y: f32, u: f32, v: f32) -> vec3<f32> {
+    return uniforms.yuv_matrix * (vec3<f32>(y, u, v) - uniforms.yuv_offset.xyz);
+}
+
+// bilinear filtering of a 16-bit texture, normalized to 0..1
+fn sample_u16(tex: texture_2d<u32>, uv: vec2<f32>) -> vec2<f32> {
+    let size = vec2<i32>(textureDimensions(tex));
+    let pos = uv * vec2<f32>(size) - vec2<f32>(0.5);
+    let base = floor(pos);
+    let f = pos - base;
+
+    let lo = clamp(vec2<i32>(base), vec2<i32>(0), size - vec2<i32>(1));
+    let hi = clamp(vec2<i32>(base) + vec2<i32>(1), vec2<i32>(0), size - vec2<i32>(1));
+    let t00 = vec2<f32>(textureLoad(tex, lo, 0).rg);
+    let t10 = vec2<f32>(textureLoad(tex, vec2<i32>(hi.x, lo.y), 0).rg);
+    let t01 = vec2<f32>(textureLoad(tex, vec2<i32>(lo.x, hi.y), 0).rg);
+    let t11 = vec2<f32>(textureLoad(tex, hi, 0).rg);
+
+    return mix(mix(t00, t10, f.x), mix(t01, t11, f.x), f.y) / 65535.0;
+}
+
+@fragment
+fn fs_nv12(in: VertexOutput) -> @location(0) vec4<f32> {
+    let y = textureSample(tex_y, s, in.uv).r;
+    let uv = textureSample(tex_uv, s, in.uv).rg;
+    return vec4<f32>(to_linear(yuv_to_rgb(y, uv.x, uv.y)), 1.0);
+}
+
+@fragment
+fn fs_planar(in: VertexOutput) -> @location(0) vec4<f32> {
+    let y = textureSample(tex_y, s, in.uv).r;
+    let u = textureSample(tex_uv, s, in.uv).r;
+    let v = textureSample(tex_v, s, in.uv).r;
+    return vec4<f32>(to_linear(yuv_to_rgb(y, u, v)), 1.0);
+}
+
+@fragment
+fn fs_yuv16(in: VertexOutput) -> @location(0) vec4<f32> {
+    let y = sample_u16(tex_y16, in.uv).r;
+    let uv = sample_u16(tex_uv16, in.uv);
+    return vec4<f32>(to_linear(yuv_to_rgb(y, uv.x, uv.y)), 1.0);
+}
+
+@fragment
+fn fs_rgba(in: VertexOutput) -> @location(0) vec4<f32> {
+    let rgb = textureSample(tex_y, s, in.uv).rgb;
+    return vec4<f32>(to_linear(rgb), 1.0);
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let yuv2r = vec3<f32>(1.164, 0.0, 1.596);
-    let yuv2g = vec3<f32>(1.164, -0.391, -0.813);
-    let yuv2b = vec3<f32>(1.164, 2.018, 0.0);
-
-    var yuv = vec3<f32>(0.0);
-    yuv.x = textureSample(tex_y, s, in.uv).r - 0.0625;
-    yuv.y = textureSample(tex_uv, s, in.uv).r - 0.5;
-    yuv.z = textureSample(tex_uv, s, in.uv).g - 0.5;
-
-    var rgb = vec3<f32>(0.0);
-    rgb.x = dot(yuv, yuv2r);
-    rgb.y = dot(yuv, yuv2g);
-    rgb.z = dot(yuv, yuv2b);
-
-    let threshold = rgb <= vec3<f32>(0.04045);
-    let hi = pow((rgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
-    let lo = rgb * vec3<f32>(1.0 / 12.92);
-    rgb = select(hi, lo, threshold);
-
-    return vec4<f32>(rgb, 1.0);
+fn fs_bgra(in: VertexOutput) -> @location(0) vec4<f32> {
+    let rgb = textureSample(tex_y, s, in.uv).bgr;
+    return vec4<f32>(to_linear(rgb), 1.0);
 }
@@ -0,0 +1,121 @@
+use crate::{Error, FrameInfo};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use iced::advanced::image::Bytes;
+use iced::widget::image as img;
+use std::time::Duration;
+
+/// How a [`Snapshot`] is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// Lossless PNG.
+    Png,
+    /// Lossy JPEG.
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// Name of the GStreamer element which encodes this format.
+    fn encoder(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "pngenc",
+            ImageFormat::Jpeg => "jpegenc",
+        }
+    }
+}
+
+/// A copy of the frame which was displayed, at full resolution, created by [`Video::snapshot`](crate::Video::snapshot).
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Width of the frame in pixels.
+    pub width: u32,
+    /// Height of the frame in pixels.
+    pub height: u32,
+    /// Pixels of the frame as (non-premultiplied) RGBA, row by row.
+    pub pixels: Bytes,
+    /// Timing of the frame, if it had a timestamp.
+    pub info: Option<FrameInfo>,
+}
+
+impl Snapshot {
+    /// Get the frame as an image which can be drawn.
+    pub fn handle(&self) -> img::Handle {
+        img::Handle::from_rgba(self.width, self.height, self.pixels.clone())
+    }
+
+    /// Encodes the frame using GStreamer's encoders (from the `png` and `jpeg` plugins).
+    ///
+    /// This blocks until the image is encoded.
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, Error> {
+        encode_rgba(self.width, self.height, &self.pixels, format)
+    }
+}
+
+/// Encodes an RGBA image with a GStreamer encoder, blocking until it is done.
+pub(crate) fn encode_rgba(
+    width: u32,
+    height: u32,
+    pixels: &Bytes,
+    format: ImageFormat,
+) -> Result<Vec<u8>, Error> {
+    gst::init()?;
+
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "RGBA")
+        .field("width", width as i32)
+        .field("height", height as i32)
+        .field("framerate", gst::Fraction::new(0, 1))
+        .build();
+    let app_src = gst_app::AppSrc::builder()
+        .caps(&caps)
+        .format(gst::Format::Time)
+        .build();
+    let video_convert = gst::ElementFactory::make("videoconvert").build()?;
+    let encoder = gst::ElementFactory::make(format.encoder()).build()?;
+    let app_sink = gst_app::AppSink::builder().sync(false).build();
+
+    let pipeline = gst::Pipeline::new();
+    let elements = [
+        app_src.upcast_ref::<gst::Element>(),
+        &video_convert,
+        &encoder,
+        app_sink.upcast_ref(),
+    ];
+    pipeline.add_many(elements)?;
+    gst::Element::link_many(elements)?;
+
+    let encoded = (|| -> Result<Vec<u8>, Error> {
+        pipeline.set_state(gst::State::Playing)?;
+
+        let mut buffer = gst::Buffer::from_slice(pixels.clone());
+        buffer
+            .get_mut()
+            .ok_or(Error::Encode)?
+            .set_pts(gst::ClockTime::ZERO);
+        app_src.push_buffer(buffer).map_err(|_| Error::Encode)?;
+        app_src.end_of_stream().map_err(|_| Error::Encode)?;
+
+        let timeout = Duration::from_secs(5);
+        let Some(sample) =
+            app_sink.try_pull_sample(gst::ClockTime::from_nseconds(timeout.as_nanos() as _))
+        else {
+            // the reason encoding failed is on the bus, if anywhere
+            let bus_error = pipeline.bus().and_then(|bus| {
+                bus.pop_filtered(&[gst::MessageType::Error])
+                    .and_then(|msg| match msg.view() {
+                        gst::MessageView::Error(err) => Some(err.error()),
+                        _ => None,
+                    })
+            });
+            return Err(bus_error.map_or(Error::Encode, Error::Glib));
+        };
+
+        let buffer = sample.buffer().ok_or(Error::Encode)?;
+        let map = buffer.map_readable()?;
+        Ok(map.to_vec())
+    })();
+
+    pipeline.set_state(gst::State::Null)?;
+    encoded
+}
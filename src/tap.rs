@@ -0,0 +1,140 @@
+use crate::frame::Frame;
+use crate::FrameInfo;
+use gstreamer_video as gst_video;
+use std::sync::mpsc;
+use std::sync::Arc;
+
+/// Most frames which can wait for a frame tap, since each one pins a buffer from the decoder's pool.
+pub const MAX_TAP_CAPACITY: usize = 2;
+
+/// Which decoded frames are handed to a frame tap, and how many may wait to be received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameTap {
+    /// Hands over one of every `every` frames (i.e., `1` hands over every frame).
+    pub every: u32,
+    /// Number of frames which can wait to be received, up to [`MAX_TAP_CAPACITY`];
+    /// any more are dropped rather than stalling playback.
+    /// With `0`, frames are only handed over while the receiver is waiting for one.
+    pub capacity: usize,
+}
+
+impl Default for FrameTap {
+    fn default() -> Self {
+        FrameTap {
+            every: 1,
+            capacity: 1,
+        }
+    }
+}
+
+impl FrameTap {
+    /// Hands over one of every `every` frames, with room for a single frame to wait.
+    pub fn every(every: u32) -> Self {
+        FrameTap {
+            every,
+            ..Default::default()
+        }
+    }
+
+    /// Set how many frames can wait to be received.
+    pub fn capacity(self, capacity: usize) -> Self {
+        FrameTap { capacity, ..self }
+    }
+}
+
+/// A decoded frame handed to a frame tap, usually read in place from GStreamer's buffer without being copied.
+///
+/// Such frames pin a buffer from the decoder's pool until they are dropped. Only a few frames of a video
+/// (including those queued for display) do so at once, so holding on to frames makes playback copy every
+/// frame instead. Copy out whatever is needed and drop them promptly.
+#[derive(Debug, Clone)]
+pub struct DecodedFrame(Arc<Frame>);
+
+/// A single plane of a [`DecodedFrame`], limited to the visible region.
+#[derive(Debug, Clone, Copy)]
+pub struct FramePlane<'a> {
+    /// Raw samples, starting at the first visible one; rows may be padded beyond the visible width.
+    pub data: &'a [u8],
+    /// Bytes per row.
+    pub stride: usize,
+    /// Width of the plane in samples (less than the frame's width for subsampled chroma).
+    pub width: u32,
+    /// Height of the plane in rows.
+    pub height: u32,
+}
+
+impl DecodedFrame {
+    /// Get the width of the visible region in pixels.
+    pub fn width(&self) -> u32 {
+        self.0.width
+    }
+
+    /// Get the height of the visible region in pixels.
+    pub fn height(&self) -> u32 {
+        self.0.height
+    }
+
+    /// Get the pixel format, which determines the number and layout of the planes.
+    pub fn format(&self) -> gst_video::VideoFormat {
+        self.0.video_info().format()
+    }
+
+    /// Get the colorimetry (range, matrix, transfer function and primaries) of the samples.
+    pub fn colorimetry(&self) -> gst_video::VideoColorimetry {
+        self.0.video_info().colorimetry()
+    }
+
+    /// Get the timing of the frame, if it has a timestamp.
+    pub fn info(&self) -> Option<FrameInfo> {
+        self.0.info()
+    }
+
+    /// Get the planes of the frame, in the order of the pixel format.
+    pub fn planes(&self) -> Vec<FramePlane<'_>> {
+        self.0
+            .planes
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| FramePlane {
+                data: self.0.plane_data(i).get(plane.offset..).unwrap_or(&[]),
+                stride: plane.stride,
+                width: plane.width,
+                height: plane.height,
+            })
+            .collect()
+    }
+}
+
+/// The worker's end of a frame tap.
+#[derive(Debug)]
+pub(crate) struct Tap {
+    sender: mpsc::SyncSender<DecodedFrame>,
+    every: u32,
+    count: u32,
+}
+
+impl Tap {
+    pub(crate) fn new(tap: FrameTap) -> (Self, mpsc::Receiver<DecodedFrame>) {
+        let (sender, receiver) = mpsc::sync_channel(tap.capacity.min(MAX_TAP_CAPACITY));
+        let tap = Tap {
+            sender,
+            every: tap.every.max(1),
+            count: 0,
+        };
+        (tap, receiver)
+    }
+
+    /// Hands over the frame if it is one of those requested, without ever blocking.
+    /// Returns `false` once the receiver is gone.
+    pub(crate) fn send(&mut self, frame: &Arc<Frame>) -> bool {
+        let due = self.count == 0;
+        self.count = (self.count + 1) % self.every;
+        if !due {
+            return true;
+        }
+        match self.sender.try_send(DecodedFrame(Arc::clone(frame))) {
+            Ok(()) | Err(mpsc::TrySendError::Full(_)) => true,
+            Err(mpsc::TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
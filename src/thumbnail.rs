@@ -0,0 +1,178 @@
+use crate::color::ToneMapping;
+use crate::convert::{to_rgba, Resize};
+use crate::frame::Frame;
+use crate::{Error, Position, Video, VideoBuilder};
+use gstreamer as gst;
+use gstreamer::glib;
+use gstreamer_app as gst_app;
+use gstreamer_app::prelude::*;
+use iced::futures::channel::mpsc;
+use iced::futures::{Stream, StreamExt};
+use iced::widget::image as img;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Thumbnails being generated on a background thread, created by [`Video::thumbnails`].
+///
+/// This is a stream of each thumbnail (or why it couldn't be generated) along with its position, in the order requested,
+/// so it can be given to `iced::Task::run`. Dropping it stops generating thumbnails.
+pub struct Thumbnails {
+    receiver: mpsc::UnboundedReceiver<(Position, Result<img::Handle, Error>)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Thumbnails {
+    /// Decodes the thumbnails with a separate pipeline, so that playback isn't affected.
+    /// `uri` is `None` if the media wasn't loaded from a URI, which fails with [`Error::Uri`].
+    pub(crate) fn spawn(
+        uri: Option<url::Url>,
+        positions: Vec<Position>,
+        resize: Resize,
+        tone_mapping: ToneMapping,
+        timeout: Duration,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let cancelled_ref = Arc::clone(&cancelled);
+        std::thread::spawn(move || {
+            let decoded = decode(
+                uri.as_ref(),
+                positions.iter().copied(),
+                timeout,
+                &cancelled_ref,
+                |position, frame| {
+                    let thumbnail = frame.map(|frame| {
+                        let (width, height, rgba) = to_rgba(&frame, resize, tone_mapping);
+                        img::Handle::from_rgba(width, height, rgba)
+                    });
+                    sender.unbounded_send((position, thumbnail)).is_ok()
+                },
+            );
+            // the media can't be loaded at all, so every position fails the same way
+            if let Err(err) = decoded {
+                for &position in &positions {
+                    if sender.unbounded_send((position, Err(err.clone()))).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Thumbnails {
+            receiver,
+            cancelled,
+        }
+    }
+
+    /// Stops generating thumbnails; the stream ends after any which are already done.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Stream for Thumbnails {
+    type Item = (Position, Result<img::Handle, Error>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for Thumbnails {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Decodes the frame at each position with a separate, video-only pipeline, until `each` returns `false`.
+/// Fails if the media can't be loaded at all.
+pub(crate) fn decode<F>(
+    uri: Option<&url::Url>,
+    positions: impl IntoIterator<Item = Position>,
+    timeout: Duration,
+    cancelled: &AtomicBool,
+    mut each: F,
+) -> Result<(), Error>
+where
+    F: FnMut(Position, Result<Frame, Error>) -> bool,
+{
+    let (pipeline, app_sink) = start(uri.ok_or(Error::Uri)?, timeout)?;
+
+    for position in positions {
+        let frame = capture(&pipeline, &app_sink, position, timeout, cancelled);
+        if cancelled.load(Ordering::SeqCst) || !each(position, frame) {
+            break;
+        }
+    }
+
+    if let Err(err) = pipeline.set_state(gst::State::Null) {
+        log::error!("failed to stop thumbnail pipeline: {err}");
+    }
+    Ok(())
+}
+
+/// Prerolls a video-only pipeline for the media.
+fn start(uri: &url::Url, timeout: Duration) -> Result<(gst::Pipeline, gst_app::AppSink), Error> {
+    let (pipeline, app_sink) = VideoBuilder::new(uri).pipeline()?;
+
+    // audio is never demuxed or decoded, since only video frames are needed
+    let flags = pipeline.property_value("flags");
+    let flags_class = glib::FlagsClass::with_type(flags.type_()).ok_or(Error::Cast)?;
+    let flags = flags_class
+        .builder_with_value(flags)
+        .and_then(|flags| flags.unset_by_nick("audio").build())
+        .ok_or(Error::Cast)?;
+    pipeline.set_property_from_value("flags", &flags);
+
+    let preroll = || -> Result<(), Error> {
+        pipeline.set_state(gst::State::Paused)?;
+        pipeline
+            .state(gst::ClockTime::from_nseconds(timeout.as_nanos() as _))
+            .0?;
+        Ok(())
+    };
+    preroll().map_err(|err| Video::start_error(&pipeline, err))?;
+
+    Ok((pipeline, app_sink))
+}
+
+/// Seeks to a position, then waits for the frame there to preroll.
+fn capture(
+    pipeline: &gst::Pipeline,
+    app_sink: &gst_app::AppSink,
+    position: Position,
+    timeout: Duration,
+    cancelled: &AtomicBool,
+) -> Result<Frame, Error> {
+    pipeline.seek_simple(
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::GenericFormattedValue::from(position),
+    )?;
+
+    let bus = pipeline.bus().ok_or(Error::Bus)?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(sample) = app_sink.try_pull_preroll(gst::ClockTime::from_mseconds(10)) {
+            return Frame::new(&sample, gst::ClockTime::ZERO);
+        }
+        // seeking past the end prerolls on EOS instead of a frame
+        if app_sink.is_eos() {
+            return Err(Error::EndOfStream);
+        }
+        if let Some(msg) = bus.pop_filtered(&[gst::MessageType::Error]) {
+            if let gst::MessageView::Error(err) = msg.view() {
+                return Err(Error::Glib(err.error()));
+            }
+        }
+        if cancelled.load(Ordering::SeqCst) {
+            return Err(Error::Cancelled);
+        }
+        if Instant::now() > deadline {
+            return Err(Error::Timeout);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::metadata::tag_string;
+use gstreamer as gst;
+use gstreamer::prelude::*;
+
+/// Kind of media stream exposed by `playbin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrackKind {
+    /// Audio stream (e.g., a dubbed language).
+    Audio,
+    /// Video stream (e.g., an alternate camera angle).
+    Video,
+    /// Text stream (i.e., subtitles).
+    Text,
+}
+
+impl TrackKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            TrackKind::Audio => "audio",
+            TrackKind::Video => "video",
+            TrackKind::Text => "text",
+        }
+    }
+
+    /// Name of the `playbin` property holding the number of streams of this kind.
+    fn count_property(self) -> String {
+        format!("n-{}", self.prefix())
+    }
+
+    /// Name of the `playbin` property holding the currently selected stream of this kind.
+    fn current_property(self) -> String {
+        format!("current-{}", self.prefix())
+    }
+
+    /// Get the number of streams of this kind, which is `0` for pipelines other than `playbin`.
+    pub(crate) fn count(self, source: &gst::Pipeline) -> i32 {
+        let property = self.count_property();
+        if source.has_property(&property, None) {
+            source.property(&property)
+        } else {
+            0
+        }
+    }
+
+    /// Get the currently selected stream of this kind, if any.
+    pub(crate) fn current(self, source: &gst::Pipeline) -> Option<i32> {
+        let property = self.current_property();
+        source
+            .has_property(&property, None)
+            .then(|| source.property::<i32>(&property))
+            .filter(|&index| index >= 0)
+    }
+
+    /// Selects a stream of this kind, returning `false` if there is no such stream.
+    pub(crate) fn select(self, source: &gst::Pipeline, index: i32) -> bool {
+        if index < 0 || index >= self.count(source) {
+            return false;
+        }
+        source.set_property(&self.current_property(), index);
+        true
+    }
+
+    /// Name of the `playbin` action signal returning the tags of a stream of this kind.
+    fn tags_signal(self) -> String {
+        format!("get-{}-tags", self.prefix())
+    }
+}
+
+/// A single audio, video, or text stream within the media.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    /// The kind of stream.
+    pub kind: TrackKind,
+    /// Index of the stream among streams of the same kind.
+    pub index: i32,
+    /// Language of the stream (usually an ISO-639 code), if tagged.
+    pub language: Option<String>,
+    /// Codec of the stream, if tagged.
+    pub codec: Option<String>,
+    /// Title of the stream, if tagged.
+    pub title: Option<String>,
+}
+
+impl Track {
+    pub(crate) fn query(source: &gst::Pipeline, kind: TrackKind, index: i32) -> Self {
+        let signal = kind.tags_signal();
+        let tags = gst::glib::subclass::SignalId::lookup(&signal, source.type_())
+            .and_then(|_| source.emit_by_name::<Option<gst::TagList>>(&signal, &[&index]));
+
+        let mut track = Track {
+            kind,
+            index,
+            language: None,
+            codec: None,
+            title: None,
+        };
+
+        if let Some(tags) = tags {
+            track.language = tag_string::<gst::tags::LanguageCode>(&tags)
+                .or_else(|| tag_string::<gst::tags::LanguageName>(&tags));
+            track.codec = match kind {
+                TrackKind::Audio => tag_string::<gst::tags::AudioCodec>(&tags),
+                TrackKind::Video => tag_string::<gst::tags::VideoCodec>(&tags),
+                TrackKind::Text => tag_string::<gst::tags::SubtitleCodec>(&tags),
+            }
+            .or_else(|| tag_string::<gst::tags::Codec>(&tags));
+            track.title = tag_string::<gst::tags::Title>(&tags);
+        }
+
+        track
+    }
+}
@@ -0,0 +1,327 @@
+use crate::color::ToneMapping;
+use crate::convert::{to_rgba, Resize};
+use crate::snapshot::encode_rgba;
+use crate::thumbnail::decode;
+use crate::{Error, ImageFormat, Position};
+use iced::advanced::image::Bytes;
+use iced::futures::channel::oneshot;
+use iced::widget::image as img;
+use std::future::Future;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// A single image of a [`Trickplay`], with thumbnails laid out in a grid.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Pixels of the image as RGBA, row by row.
+    pub pixels: Bytes,
+    /// The image, ready to be drawn.
+    pub handle: img::Handle,
+}
+
+impl SpriteSheet {
+    /// Encodes the sheet (e.g., as the JPEG referred to by [`Trickplay::webvtt`]); see [`Snapshot::encode`](crate::Snapshot::encode).
+    ///
+    /// This blocks until the image is encoded.
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, Error> {
+        encode_rgba(self.width, self.height, &self.pixels, format)
+    }
+}
+
+/// Thumbnails of a video at a fixed interval, packed into one or more sprite sheets (e.g., for previews when seeking).
+///
+/// Created by [`Video::trickplay`](crate::Video::trickplay).
+#[derive(Debug, Clone)]
+pub struct Trickplay {
+    /// Time between thumbnails.
+    pub interval: Duration,
+    /// Duration of the media, at which the last thumbnail ends.
+    pub duration: Duration,
+    /// Width of each thumbnail in pixels.
+    pub tile_width: u32,
+    /// Height of each thumbnail in pixels.
+    pub tile_height: u32,
+    /// Number of thumbnails in each row of a sheet.
+    pub columns: u32,
+    /// Maximum number of rows in a sheet.
+    pub rows: u32,
+    /// Number of thumbnails across all sheets.
+    pub count: usize,
+    /// The sprite sheets, in order; all but the last are full.
+    pub sheets: Vec<SpriteSheet>,
+}
+
+impl Trickplay {
+    /// Decodes the thumbnails with a separate pipeline on a background thread, then packs them.
+    /// The media is opened again from `uri`, so a video created from a custom pipeline (without one) fails with [`Error::Uri`].
+    pub(crate) fn spawn(
+        uri: Option<url::Url>,
+        layout: Trickplay,
+        resize: Resize,
+        tone_mapping: ToneMapping,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Trickplay, Error>> {
+        let (sender, receiver) = oneshot::channel();
+
+        std::thread::spawn(move || {
+            let result = layout.pack(uri.as_ref(), resize, tone_mapping, timeout, || {
+                sender.is_canceled()
+            });
+            let _ = sender.send(result);
+        });
+
+        async move { receiver.await.unwrap_or(Err(Error::Cancelled)) }
+    }
+
+    /// Creates the layout of the sprite sheets, without any sheets yet.
+    pub(crate) fn new(
+        interval: Duration,
+        duration: Duration,
+        (tile_width, tile_height): (u32, u32),
+        columns: u32,
+        rows: u32,
+    ) -> Result<Self, Error> {
+        if interval.is_zero() {
+            return Err(Error::Interval);
+        }
+        if duration.is_zero() {
+            return Err(Error::Duration);
+        }
+        Ok(Trickplay {
+            interval,
+            duration,
+            tile_width,
+            tile_height,
+            columns: columns.max(1),
+            rows: rows.max(1),
+            count: duration.as_nanos().div_ceil(interval.as_nanos()) as usize,
+            sheets: Vec::new(),
+        })
+    }
+
+    fn pack(
+        mut self,
+        uri: Option<&url::Url>,
+        resize: Resize,
+        tone_mapping: ToneMapping,
+        timeout: Duration,
+        is_cancelled: impl Fn() -> bool,
+    ) -> Result<Self, Error> {
+        let per_sheet = (self.columns * self.rows) as usize;
+        let sheet_width = self.tile_width * self.columns;
+        let mut sheets: Vec<(u32, Vec<u8>)> = (0..self.count.div_ceil(per_sheet))
+            .map(|sheet| {
+                let tiles = (self.count - sheet * per_sheet).min(per_sheet) as u32;
+                let height = tiles.div_ceil(self.columns) * self.tile_height;
+                (height, vec![0; (sheet_width * height * 4) as usize])
+            })
+            .collect();
+
+        let positions = (0..self.count).map(|i| Position::Time(self.interval * i as u32));
+        let mut index = 0;
+        decode(
+            uri,
+            positions,
+            timeout,
+            &AtomicBool::new(false),
+            |_, frame| {
+                match frame {
+                    Ok(frame) => {
+                        let (sheet, rect) = self.tile(index).expect("tile within count");
+                        // the resolution may have changed mid-stream, so the thumbnail is fit to the tile
+                        let resize = Resize {
+                            width: rect.width,
+                            height: rect.height,
+                            ..resize
+                        };
+                        let (width, height, rgba) = to_rgba(&frame, resize, tone_mapping);
+                        let src_stride = width as usize * 4;
+                        let width = width.min(rect.width) as usize;
+                        let height = height.min(rect.height);
+                        let dst_stride = sheet_width as usize * 4;
+                        let pixels = &mut sheets[sheet].1;
+                        for row in 0..height as usize {
+                            let src = row * src_stride;
+                            let dst = (rect.y as usize + row) * dst_stride + rect.x as usize * 4;
+                            pixels[dst..dst + width * 4]
+                                .copy_from_slice(&rgba[src..src + width * 4]);
+                        }
+                    }
+                    // positions near the end may be past the last frame; the rest of the tiles are left blank
+                    Err(Error::EndOfStream) => return false,
+                    // a single broken frame shouldn't ruin the whole sheet, so its tile is left blank
+                    Err(err) => log::warn!("failed to generate trickplay thumbnail: {err}"),
+                }
+                index += 1;
+                !is_cancelled()
+            },
+        )?;
+
+        self.sheets = sheets
+            .into_iter()
+            .map(|(height, pixels)| {
+                let pixels = Bytes::from(pixels);
+                SpriteSheet {
+                    width: sheet_width,
+                    height,
+                    handle: img::Handle::from_rgba(sheet_width, height, pixels.clone()),
+                    pixels,
+                }
+            })
+            .collect();
+        Ok(self)
+    }
+
+    /// Get the sheet (by index) and the region within it of a thumbnail (by index).
+    pub fn tile(&self, index: usize) -> Option<(usize, iced::Rectangle<u32>)> {
+        if index >= self.count {
+            return None;
+        }
+        let per_sheet = (self.columns * self.rows) as usize;
+        let within = (index % per_sheet) as u32;
+        Some((
+            index / per_sheet,
+            iced::Rectangle {
+                x: within % self.columns * self.tile_width,
+                y: within / self.columns * self.tile_height,
+                width: self.tile_width,
+                height: self.tile_height,
+            },
+        ))
+    }
+
+    /// Get the sheet (by index) and the region within it of the thumbnail showing a given time.
+    pub fn tile_at(&self, time: Duration) -> Option<(usize, iced::Rectangle<u32>)> {
+        let index = (time.as_nanos() / self.interval.as_nanos()) as usize;
+        self.tile(index.min(self.count.saturating_sub(1)))
+    }
+
+    /// Generates a WebVTT thumbnail track, where each cue refers to its thumbnail as `sheet_url#xywh=x,y,w,h`.
+    ///
+    /// `sheet_url` gives the URL of each sheet (by index) once saved (see [`SpriteSheet::encode`]), e.g., `|i| format!("sprite-{i}.jpg")`.
+    pub fn webvtt(&self, sheet_url: impl Fn(usize) -> String) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for index in 0..self.count {
+            let Some((sheet, rect)) = self.tile(index) else {
+                break;
+            };
+            let start = self.interval * index as u32;
+            let end = (start + self.interval).min(self.duration);
+            vtt += &format!(
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                vtt_timestamp(start),
+                vtt_timestamp(end),
+                sheet_url(sheet),
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+            );
+        }
+        vtt
+    }
+}
+
+fn vtt_timestamp(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        time.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 95 seconds of thumbnails every 10 seconds, in 3x2 sheets of 160x90 tiles.
+    fn layout() -> Trickplay {
+        Trickplay::new(
+            Duration::from_secs(10),
+            Duration::from_secs(95),
+            (160, 90),
+            3,
+            2,
+        )
+        .unwrap()
+    }
+
+    fn rect(x: u32, y: u32) -> iced::Rectangle<u32> {
+        iced::Rectangle {
+            x,
+            y,
+            width: 160,
+            height: 90,
+        }
+    }
+
+    #[test]
+    fn zero_durations() {
+        let zero = Trickplay::new(Duration::ZERO, Duration::from_secs(1), (1, 1), 1, 1);
+        assert!(matches!(zero, Err(Error::Interval)));
+        let zero = Trickplay::new(Duration::from_secs(1), Duration::ZERO, (1, 1), 1, 1);
+        assert!(matches!(zero, Err(Error::Duration)));
+    }
+
+    #[test]
+    fn tiles_fill_rows_then_sheets() {
+        let trickplay = layout();
+        assert_eq!(trickplay.tile(0), Some((0, rect(0, 0))));
+        assert_eq!(trickplay.tile(2), Some((0, rect(320, 0))));
+        assert_eq!(trickplay.tile(3), Some((0, rect(0, 90))));
+        assert_eq!(trickplay.tile(5), Some((0, rect(320, 90))));
+        assert_eq!(trickplay.tile(6), Some((1, rect(0, 0))));
+        assert_eq!(trickplay.tile(9), Some((1, rect(0, 90))));
+        assert_eq!(trickplay.tile(10), None);
+    }
+
+    #[test]
+    fn tile_at_time() {
+        let trickplay = layout();
+        assert_eq!(trickplay.tile_at(Duration::ZERO), trickplay.tile(0));
+        assert_eq!(
+            trickplay.tile_at(Duration::from_millis(9999)),
+            trickplay.tile(0)
+        );
+        assert_eq!(
+            trickplay.tile_at(Duration::from_secs(10)),
+            trickplay.tile(1)
+        );
+        assert_eq!(
+            trickplay.tile_at(Duration::from_secs(94)),
+            trickplay.tile(9)
+        );
+        // past the end shows the last thumbnail
+        assert_eq!(
+            trickplay.tile_at(Duration::from_secs(600)),
+            trickplay.tile(9)
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(vtt_timestamp(Duration::ZERO), "00:00:00.000");
+        assert_eq!(vtt_timestamp(Duration::from_millis(61_250)), "00:01:01.250");
+        assert_eq!(
+            vtt_timestamp(Duration::from_secs(3 * 3600 + 59)),
+            "03:00:59.000"
+        );
+    }
+
+    #[test]
+    fn webvtt_cues() {
+        let vtt = layout().webvtt(|sheet| format!("sprite-{sheet}.jpg"));
+        assert!(vtt.starts_with(
+            "WEBVTT\n\n00:00:00.000 --> 00:00:10.000\nsprite-0.jpg#xywh=0,0,160,90\n"
+        ));
+        // the last cue ends with the video
+        assert!(vtt.ends_with("\n00:01:30.000 --> 00:01:35.000\nsprite-1.jpg#xywh=0,90,160,90\n"));
+    }
+}
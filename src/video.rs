@@ -1,14 +1,28 @@
-use crate::Error;
+use crate::chapter::chapter_at;
+use crate::color::ToneMapping;
+use crate::convert::{to_rgba, Resize};
+use crate::format::PixelFormat;
+use crate::frame::{frame_to_time, time_to_frame, Frame, FrameQueue};
+use crate::tap::{DecodedFrame, FrameTap, Tap};
+use crate::{
+    Chapter, Error, FrameInfo, Metadata, MissingPlugin, Snapshot, Thumbnails, Track, TrackKind,
+    Trickplay, VideoBuilder, VideoLoader,
+};
 use gstreamer as gst;
 use gstreamer_app as gst_app;
 use gstreamer_app::prelude::*;
-use iced::widget::image as img;
+use gstreamer_base as gst_base;
+use gstreamer_base::prelude::BaseSinkExt;
+use gstreamer_video as gst_video;
 use std::cell::RefCell;
-use std::num::NonZeroU8;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, Instant};
 
+/// How many frames ahead of time the worker gets frames from the appsink.
+const FRAME_LOOKAHEAD: u64 = 2;
+
 /// Position in the media.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Position {
@@ -41,6 +55,165 @@ impl From<u64> for Position {
     }
 }
 
+/// How the media repeats once it reaches the end.
+///
+/// Loops use segment seeks, so there is no gap between the end and the start of the next loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LoopMode {
+    /// Play once, then stop at the end.
+    #[default]
+    None,
+    /// Loop the entire media forever.
+    Infinite,
+    /// Loop the entire media a number of times (i.e., `Count(1)` plays the media twice), then stop at the end.
+    Count(u32),
+    /// Loop between two points in the media (A-B loop) forever.
+    Region {
+        /// Point A, where each loop starts.
+        start: std::time::Duration,
+        /// Point B, where each loop ends.
+        end: std::time::Duration,
+    },
+    /// Play forwards to the end, then backwards to the start, forever.
+    PingPong,
+}
+
+/// Name of the application message posted once the media looped (with the loop `count`) or ended instead.
+pub(crate) const LOOPED_MESSAGE: &str = "iced-video-player-looped";
+
+/// Playback rate and loop state, shared with the bus so that loops don't wait for the UI.
+#[derive(Debug)]
+pub(crate) struct Looping {
+    pub(crate) mode: LoopMode,
+    pub(crate) count: u32,
+    pub(crate) ping_pong_reversed: bool,
+    pub(crate) speed: f64,
+    /// Rate of the current segment, which is negative while playing backwards.
+    pub(crate) segment_rate: f64,
+}
+
+impl Looping {
+    /// The rate the media should be playing at, accounting for ping-pong loops.
+    pub(crate) fn playback_rate(&self) -> f64 {
+        if self.ping_pong_reversed {
+            -self.speed
+        } else {
+            self.speed
+        }
+    }
+
+    /// Segment seeks make GStreamer post `SegmentDone` instead of `Eos`, allowing seamless loops.
+    fn flags(&self) -> gst::SeekFlags {
+        if self.mode == LoopMode::None {
+            gst::SeekFlags::empty()
+        } else {
+            gst::SeekFlags::SEGMENT
+        }
+    }
+
+    /// Start and stop of the segment to play, given the loop mode.
+    fn bounds(&self) -> (gst::ClockTime, gst::SeekType, gst::ClockTime) {
+        match self.mode {
+            LoopMode::Region { start, end } => (
+                gst::ClockTime::from_nseconds(start.as_nanos() as _),
+                gst::SeekType::Set,
+                gst::ClockTime::from_nseconds(end.as_nanos() as _),
+            ),
+            _ => (
+                gst::ClockTime::ZERO,
+                gst::SeekType::End,
+                gst::ClockTime::ZERO,
+            ),
+        }
+    }
+
+    /// Whether the media should loop again once it reaches the end.
+    pub(crate) fn loops_remaining(&self) -> bool {
+        match self.mode {
+            LoopMode::None => false,
+            LoopMode::Count(count) => self.count < count,
+            _ => true,
+        }
+    }
+
+    /// Loops back once the current segment is done.
+    /// Returns the number of loops so far, or `None` if the media should end instead.
+    pub(crate) fn loop_segment(&mut self, pipeline: &gst::Pipeline) -> Result<Option<u32>, Error> {
+        if !self.loops_remaining() {
+            return Ok(None);
+        }
+        self.count += 1;
+
+        let (start, stop_type, stop) = self.bounds();
+        let mut rate = self.speed;
+        if self.mode == LoopMode::PingPong {
+            self.ping_pong_reversed = !self.ping_pong_reversed;
+            rate = self.playback_rate();
+        }
+
+        // the last of a fixed number of loops isn't a segment so that it ends with a regular EOS
+        let flags = if self.loops_remaining() {
+            gst::SeekFlags::SEGMENT
+        } else {
+            gst::SeekFlags::empty()
+        };
+
+        // not flushing; the new segment is queued right after the current one
+        pipeline.seek(
+            rate,
+            flags | gst::SeekFlags::ACCURATE,
+            gst::SeekType::Set,
+            start,
+            stop_type,
+            stop,
+        )?;
+        self.segment_rate = rate;
+
+        Ok(Some(self.count))
+    }
+}
+
+/// Locks shared state, even if another thread panicked while holding it.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Loops the media as soon as a segment is done, posting [`LOOPED_MESSAGE`] for the UI afterwards.
+///
+/// The seek happens on its own thread, since seeking from the streaming thread which posted the message could deadlock.
+/// If the pipeline already has a sync handler, this one isn't installed and the UI loops the media instead.
+fn watch_segments(pipeline: &gst::Pipeline, looping: &Arc<Mutex<Looping>>) -> Result<(), Error> {
+    let bus = pipeline.bus().ok_or(Error::Bus)?;
+    let pipeline = pipeline.downgrade();
+    let looping = Arc::clone(looping);
+    bus.set_sync_handler(move |_, msg| {
+        if let gst::MessageView::SegmentDone(_) = msg.view() {
+            let pipeline = pipeline.clone();
+            let looping = Arc::clone(&looping);
+            std::thread::spawn(move || {
+                let Some(pipeline) = pipeline.upgrade() else {
+                    return;
+                };
+                let looped = lock(&looping).loop_segment(&pipeline);
+                let mut structure = gst::Structure::builder(LOOPED_MESSAGE);
+                match looped {
+                    Ok(Some(count)) => structure = structure.field("count", count),
+                    Ok(None) => {}
+                    Err(err) => {
+                        log::error!("cannot loop stream (can't seek): {err:#?}");
+                        return;
+                    }
+                }
+                let _ = pipeline.post_message(gst::message::Application::new(structure.build()));
+            });
+            // handled here, so it must not be looped again by the UI
+            return gst::BusSyncReply::Drop;
+        }
+        gst::BusSyncReply::Pass
+    });
+    Ok(())
+}
+
 pub(crate) struct Internal {
     pub(crate) id: u64,
 
@@ -51,35 +224,99 @@ pub(crate) struct Internal {
 
     pub(crate) width: i32,
     pub(crate) height: i32,
-    pub(crate) framerate: f64,
+    pub(crate) framerate: gst::Fraction,
     pub(crate) duration: std::time::Duration,
-    pub(crate) speed: f64,
-    pub(crate) sync_av: bool,
 
-    pub(crate) frame: Arc<Mutex<Vec<u8>>>,
-    pub(crate) upload_frame: Arc<AtomicBool>,
-    pub(crate) last_frame_time: Arc<Mutex<Instant>>,
+    pub(crate) frame_queue: Arc<FrameQueue>,
+    pub(crate) frame: Option<Arc<Frame>>,
+    pub(crate) upload_frame: bool,
+    /// Hands a new frame tap (or `None` to remove it) to the worker.
+    pub(crate) frame_tap: std::sync::mpsc::Sender<Option<Tap>>,
     pub(crate) paused: Arc<AtomicBool>,
-    pub(crate) looping: bool,
+    pub(crate) looping: Arc<Mutex<Looping>>,
     pub(crate) is_eos: bool,
     pub(crate) restart_stream: bool,
-    pub(crate) sync_av_avg: u64,
-    pub(crate) sync_av_counter: u64,
+
+    /// URI of the media which the metadata and chapters belong to.
+    pub(crate) stream_uri: Option<url::Url>,
+    pub(crate) metadata: Metadata,
+    pub(crate) chapters: Vec<Chapter>,
+    pub(crate) chapter: Option<usize>,
+    pub(crate) buffering: Option<i32>,
+    pub(crate) live: bool,
+    pub(crate) seeking: bool,
+    /// Frames to step once the segment has been reversed for stepping backwards.
+    pub(crate) pending_step: Option<u64>,
+    /// Timestamp of the frame a step started from, until a different frame is displayed.
+    pub(crate) stepping: Option<Option<gst::ClockTime>>,
+    pub(crate) tone_mapping: ToneMapping,
 }
 
 impl Internal {
-    pub(crate) fn seek(&self, position: impl Into<Position>, accurate: bool) -> Result<(), Error> {
-        let position = position.into();
+    /// Takes the frame which is due from the worker, if there is a new one, to be uploaded on the next draw.
+    pub(crate) fn take_frame(&mut self) -> bool {
+        // frames are displayed once the pipeline clock reaches them, but while paused the clock doesn't matter
+        let now = if self.paused.load(Ordering::SeqCst) {
+            None
+        } else {
+            self.source.current_running_time()
+        };
+        let Some(frame) = self.frame_queue.take(now) else {
+            return false;
+        };
+        self.frame = Some(frame);
+        self.upload_frame = true;
+        true
+    }
+
+    /// Checks if the media supports seeking (and therefore changing speed).
+    pub(crate) fn check_seekable(&self) -> Result<(), Error> {
+        if self.live {
+            return Err(Error::Live);
+        }
+        let mut query = gst::query::Seeking::new(gst::Format::Time);
+        if self.source.query(&mut query) && !query.result().0 {
+            return Err(Error::NotSeekable);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn seek(
+        &mut self,
+        position: impl Into<Position>,
+        accurate: bool,
+    ) -> Result<(), Error> {
+        self.check_seekable()?;
+        let mut looping = lock(&self.looping);
+        let (start, stop_type, stop) = looping.bounds();
+        let position = match position.into() {
+            // the end of an A-B loop is in time, so the start must be too
+            Position::Frame(frame) if stop_type == gst::SeekType::Set => {
+                Position::Time(frame_to_time(frame, self.framerate))
+            }
+            position => position,
+        };
+        // a segment can't start after it stops, so seeking past the end of an A-B loop goes back to its start
+        let position = match position {
+            Position::Time(time)
+                if stop_type == gst::SeekType::Set
+                    && time.as_nanos() >= stop.nseconds() as u128 =>
+            {
+                Position::Time(Duration::from_nanos(start.nseconds()))
+            }
+            position => position,
+        };
         // gstreamer complains if the start & end value types aren't the same
         let end = match &position {
-            Position::Time(_) => Position::Time(std::time::Duration::ZERO),
-            Position::Frame(_) => Position::Frame(0),
+            Position::Time(_) => gst::GenericFormattedValue::from(stop),
+            Position::Frame(_) => gst::GenericFormattedValue::from(Position::Frame(0)),
         };
 
         self.source.seek(
-            self.speed,
+            looping.speed,
             gst::SeekFlags::FLUSH
                 | gst::SeekFlags::KEY_UNIT
+                | looping.flags()
                 | if accurate {
                     gst::SeekFlags::ACCURATE
                 } else {
@@ -87,71 +324,286 @@ impl Internal {
                 },
             gst::SeekType::Set,
             gst::GenericFormattedValue::from(position),
-            gst::SeekType::End,
-            gst::GenericFormattedValue::from(end),
+            stop_type,
+            end,
         )?;
+        self.frame_queue.clear();
+        looping.segment_rate = looping.speed;
+        looping.ping_pong_reversed = false;
+        drop(looping);
+        self.seeking = true;
+        self.pending_step = None;
+        self.stepping = None;
         Ok(())
     }
 
+    pub(crate) fn position(&self) -> std::time::Duration {
+        // live streams have no meaningful stream position, so use the time since playback started
+        let position = if self.live {
+            self.source.current_running_time()
+        } else {
+            self.source.query_position::<gst::ClockTime>()
+        };
+        std::time::Duration::from_nanos(position.map_or(0, |pos| pos.nseconds()))
+    }
+
+    /// Get the URI of the media being played, if it was loaded from one (i.e., by a `playbin`).
+    pub(crate) fn uri(&self) -> Option<url::Url> {
+        self.source
+            .has_property("current-uri", None)
+            .then(|| self.source.property::<Option<String>>("current-uri"))
+            .flatten()
+            .and_then(|uri| url::Url::parse(&uri).ok())
+    }
+
+    pub(crate) fn update_duration(&mut self) {
+        self.duration = std::time::Duration::from_nanos(
+            self.source
+                .query_duration::<gst::ClockTime>()
+                .map_or(0, |duration| duration.nseconds()),
+        );
+    }
+
+    /// Replaces the media being played, reusing the pipeline.
+    pub(crate) fn set_uri(&mut self, uri: &url::Url) -> Result<(), Error> {
+        // start playing the new media unless it was explicitly paused
+        let paused = self.paused.load(Ordering::SeqCst) && !self.is_eos;
+
+        self.source.set_state(gst::State::Ready)?;
+        self.source.set_property("uri", uri.as_str());
+        self.frame_queue.clear();
+
+        self.is_eos = false;
+        self.restart_stream = false;
+        {
+            let mut looping = lock(&self.looping);
+            looping.segment_rate = looping.speed;
+            looping.ping_pong_reversed = false;
+            looping.count = 0;
+        }
+        self.pending_step = None;
+        self.stepping = None;
+        self.buffering = None;
+        // the displayed frame is kept until the new media has one, but it no longer describes the media
+        self.frame = None;
+        self.reset_stream(Some(uri.clone()));
+
+        self.set_paused(paused)
+    }
+
+    /// Forgets what is known about the media once a different URI is playing
+    /// (e.g., after a gapless transition), before anything about the new media is recorded.
+    pub(crate) fn follow_uri(&mut self) {
+        let uri = self.uri();
+        if uri != self.stream_uri {
+            self.reset_stream(uri);
+        }
+    }
+
+    fn reset_stream(&mut self, uri: Option<url::Url>) {
+        self.stream_uri = uri;
+        self.metadata = Metadata::default();
+        self.chapters.clear();
+        self.chapter = None;
+    }
+
     pub(crate) fn set_speed(&mut self, speed: f64) -> Result<(), Error> {
+        self.check_seekable()?;
         let Some(position) = self.source.query_position::<gst::ClockTime>() else {
             return Err(Error::Caps);
         };
-        if speed > 0.0 {
-            self.source.seek(
-                speed,
-                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
-                gst::SeekType::Set,
-                position,
-                gst::SeekType::End,
-                gst::ClockTime::from_seconds(0),
-            )?;
+        // keep going in the current direction of a ping-pong loop
+        let rate = if lock(&self.looping).ping_pong_reversed {
+            -speed
+        } else {
+            speed
+        };
+        self.seek_rate(rate, position)?;
+        lock(&self.looping).speed = speed;
+        Ok(())
+    }
+
+    /// Changes the rate (and thus direction) of the current segment, keeping the current position.
+    fn seek_rate(&mut self, rate: f64, position: gst::ClockTime) -> Result<(), Error> {
+        let mut looping = lock(&self.looping);
+        let (start, stop_type, stop) = looping.bounds();
+        let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE | looping.flags();
+        if rate > 0.0 {
+            self.source
+                .seek(rate, flags, gst::SeekType::Set, position, stop_type, stop)?;
         } else {
             self.source.seek(
-                speed,
-                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                rate,
+                flags,
                 gst::SeekType::Set,
-                gst::ClockTime::from_seconds(0),
+                start,
                 gst::SeekType::Set,
                 position,
             )?;
         }
-        self.speed = speed;
+        self.frame_queue.clear();
+        looping.segment_rate = rate;
+        drop(looping);
+        self.seeking = true;
+        Ok(())
+    }
+
+    pub(crate) fn set_loop_mode(&mut self, loop_mode: LoopMode) -> Result<(), Error> {
+        if loop_mode != LoopMode::None {
+            self.check_seekable()?;
+        }
+        if let LoopMode::Region { start, end } = loop_mode {
+            // the duration is unknown (zero) for some streams, in which case only the order is checked
+            if start >= end || (!self.duration.is_zero() && end > self.duration) {
+                return Err(Error::LoopRegion);
+            }
+        }
+        let (previous, speed, (start, _, stop)) = {
+            let mut looping = lock(&self.looping);
+            let previous = (looping.mode, looping.count, looping.ping_pong_reversed);
+            looping.mode = loop_mode;
+            looping.count = 0;
+            looping.ping_pong_reversed = false;
+            (previous, looping.speed, looping.bounds())
+        };
+
+        if loop_mode != LoopMode::None {
+            // start a segment so that the first loop is already seamless
+            let position = self
+                .source
+                .query_position::<gst::ClockTime>()
+                .unwrap_or(start);
+            let position = match loop_mode {
+                LoopMode::Region { .. } if position < start || position >= stop => start,
+                _ => position,
+            };
+            if let Err(err) = self.seek_rate(speed, position) {
+                let mut looping = lock(&self.looping);
+                (looping.mode, looping.count, looping.ping_pong_reversed) = previous;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn step_frames(&mut self, frames: i64) -> Result<(), Error> {
+        if frames == 0 {
+            return Ok(());
+        }
+        if self.live {
+            return Err(Error::Live);
+        }
+        if !self.paused.load(Ordering::SeqCst) {
+            self.set_paused(true)?;
+        }
+
+        // step events always go in the direction of the current segment, so reverse it if needed
+        let (speed, segment_rate) = {
+            let looping = lock(&self.looping);
+            (looping.speed, looping.segment_rate)
+        };
+        let rate = if frames > 0 {
+            speed.abs()
+        } else {
+            -speed.abs()
+        };
+        self.stepping = Some(self.frame.as_ref().and_then(|frame| frame.pts));
+        if rate.is_sign_positive() != segment_rate.is_sign_positive() {
+            let position = self
+                .source
+                .query_position::<gst::ClockTime>()
+                .ok_or(Error::Duration)?;
+            self.seek_rate(rate, position)?;
+            // the step would be lost in the flush, so it is sent once the seek is done
+            self.pending_step = Some(frames.unsigned_abs());
+            return Ok(());
+        }
+        self.send_step(frames.unsigned_abs())
+    }
+
+    /// Steps by a number of frames in the direction of the current segment.
+    /// The new frame is taken as usual once it arrives, which ends the step.
+    pub(crate) fn send_step(&mut self, frames: u64) -> Result<(), Error> {
+        self.frame_queue.clear();
+        if !self.source.send_event(gst::event::Step::new(
+            gst::format::Buffers::from_u64(frames),
+            1.0,
+            true,
+            false,
+        )) {
+            self.stepping = None;
+            return Err(Error::Step);
+        }
         Ok(())
     }
 
+    /// Ends a step once a different frame than the one it started from is displayed.
+    pub(crate) fn step_done(&mut self) -> bool {
+        let pts = self.frame.as_ref().and_then(|frame| frame.pts);
+        if self.pending_step.is_some() || self.stepping.is_none_or(|from| from == pts) {
+            return false;
+        }
+        self.stepping = None;
+        true
+    }
+
     pub(crate) fn restart_stream(&mut self) -> Result<(), Error> {
         self.is_eos = false;
-        self.set_paused(false);
-        self.seek(0, false)?;
+        self.set_paused(false)?;
+        let (start, _, _) = lock(&self.looping).bounds();
+        self.seek(std::time::Duration::from_nanos(start.nseconds()), false)?;
         Ok(())
     }
 
-    pub(crate) fn set_paused(&mut self, paused: bool) {
+    pub(crate) fn set_paused(&mut self, paused: bool) -> Result<(), Error> {
+        // undo any reversal of the segment done for stepping backwards
+        let rate = {
+            let looping = lock(&self.looping);
+            (looping.segment_rate != looping.playback_rate()).then(|| looping.playback_rate())
+        };
+        if let Some(rate) = rate.filter(|_| !paused) {
+            if let Some(position) = self.source.query_position::<gst::ClockTime>() {
+                if let Err(err) = self.seek_rate(rate, position) {
+                    log::error!("failed to restore playback direction: {err}");
+                }
+            }
+        }
+
         self.source
-            .set_state(if paused {
+            .set_state(if paused || self.buffering.is_some() {
                 gst::State::Paused
             } else {
                 gst::State::Playing
-            })
-            .unwrap(/* state was changed in ctor; state errors caught there */);
+            })?;
         self.paused.store(paused, Ordering::SeqCst);
 
         // Set restart_stream flag to make the stream restart on the next Message::NextFrame
         if self.is_eos && !paused {
             self.restart_stream = true;
         }
+        Ok(())
     }
 
-    /// Syncs audio with video when there is (inevitably) latency presenting the frame.
-    pub(crate) fn set_av_offset(&mut self, offset: Duration) {
-        if self.sync_av {
-            self.sync_av_counter += 1;
-            self.sync_av_avg = self.sync_av_avg * (self.sync_av_counter - 1) / self.sync_av_counter
-                + offset.as_nanos() as u64 / self.sync_av_counter;
-            if self.sync_av_counter % 128 == 0 {
-                self.source
-                    .set_property("av-offset", -(self.sync_av_avg as i64));
+    /// Holds playback while the network stream is buffering, resuming once it is done.
+    pub(crate) fn set_buffering(&mut self, percent: i32) {
+        // live sources can't wait for data, they would only fall behind
+        if self.live {
+            return;
+        }
+        let playing = !self.paused.load(Ordering::SeqCst);
+        let state = if percent < 100 {
+            let started = self.buffering.is_none();
+            self.buffering = Some(percent);
+            (started && playing).then_some(gst::State::Paused)
+        } else {
+            let finished = self.buffering.take().is_some();
+            (finished && playing).then_some(gst::State::Playing)
+        };
+
+        // the user-facing paused state is left alone
+        if let Some(state) = state {
+            if let Err(err) = self.source.set_state(state) {
+                log::error!("failed to change state for buffering: {err}");
             }
         }
     }
@@ -178,69 +630,150 @@ impl Drop for Video {
 
 impl Video {
     /// Create a new video player from a given video which loads from `uri`.
-    /// Note that live sources will report the duration to be zero.
+    /// Note that live sources will report the duration to be zero; see [`Video::is_live`].
+    ///
+    /// This blocks until the media is ready to play (for up to 5 seconds); see [`Video::new_async`] to avoid this.
+    /// Use [`Video::builder`] to configure how the video starts.
     pub fn new(uri: &url::Url) -> Result<Self, Error> {
-        gst::init()?;
+        VideoBuilder::new(uri).build()
+    }
+
+    /// Starts loading a video from `uri` on a background thread, without blocking.
+    ///
+    /// The returned [`VideoLoader`] is a future resolving to the video once it is ready to play.
+    /// Loading can be cancelled with [`VideoLoader::cancel`] or by dropping the loader.
+    pub fn new_async(uri: &url::Url) -> VideoLoader {
+        VideoBuilder::new(uri).build_async()
+    }
+
+    /// Start configuring a video which loads from `uri` (e.g., to start paused or at a given position).
+    pub fn builder(uri: &url::Url) -> VideoBuilder {
+        VideoBuilder::new(uri)
+    }
+
+    /// Prerolls the pipeline, returning whether it is live and the negotiated resolution and framerate.
+    fn start(
+        pipeline: &gst::Pipeline,
+        pad: &gst::Pad,
+        timeout: Duration,
+    ) -> Result<(bool, i32, i32, gst::Fraction), Error> {
+        // live sources can't preroll, which is signalled when pausing
+        let mut live =
+            pipeline.set_state(gst::State::Paused)? == gst::StateChangeSuccess::NoPreroll;
+        if live {
+            pipeline.set_state(gst::State::Playing)?;
+        }
+
+        // wait until the decoder gets the source capabilities
+        match pipeline
+            .state(gst::ClockTime::from_nseconds(timeout.as_nanos() as u64))
+            .0?
+        {
+            gst::StateChangeSuccess::NoPreroll => live = true,
+            // still changing state once the timeout is up
+            gst::StateChangeSuccess::Async => return Err(Error::Timeout),
+            gst::StateChangeSuccess::Success => {}
+        }
+
+        // live sources only have caps once the first buffer arrives
+        let deadline = Instant::now() + timeout;
+        let caps = loop {
+            match pad.current_caps() {
+                Some(caps) => break caps,
+                None if live && Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                None if live => return Err(Error::Timeout),
+                None => return Err(Error::Caps),
+            }
+        };
 
-        let pipeline = format!("playbin uri=\"{}\" video-sink=\"videoscale ! videoconvert ! appsink name=iced_video drop=true caps=video/x-raw,format=NV12,pixel-aspect-ratio=1/1\"", uri.as_str());
-        let pipeline = gst::parse::launch(pipeline.as_ref())?
-            .downcast::<gst::Pipeline>()
-            .map_err(|_| Error::Cast)?;
+        // extract resolution and framerate
+        let (_, info) = video_format(&caps)?;
+        let (width, height) = (info.width() as i32, info.height() as i32);
+        let s = caps.structure(0).ok_or(Error::Caps)?;
+        let framerate = s
+            .get::<gst::Fraction>("framerate")
+            .map_err(|_| Error::Caps)?;
+        let fps = framerate.numer() as f64 / framerate.denom() as f64;
 
-        let video_sink: gst::Element = pipeline.property("video-sink");
-        let pad = video_sink.pads().get(0).cloned().unwrap();
-        let pad = pad.dynamic_cast::<gst::GhostPad>().unwrap();
-        let bin = pad
-            .parent_element()
-            .unwrap()
-            .downcast::<gst::Bin>()
-            .unwrap();
-        let app_sink = bin.by_name("iced_video").unwrap();
-        let app_sink = app_sink.downcast::<gst_app::AppSink>().unwrap();
+        if fps.is_nan() || fps.is_infinite() || fps < 0.0 || fps.abs() < f64::EPSILON {
+            return Err(Error::Framerate(fps));
+        }
 
-        Self::from_gst_pipeline(pipeline, app_sink)
+        Ok((live, width, height, framerate))
+    }
+
+    /// Stops a pipeline which failed to start, finding the most helpful explanation of why it failed.
+    pub(crate) fn start_error(pipeline: &gst::Pipeline, err: Error) -> Error {
+        let mut missing_plugins = Vec::new();
+        let mut bus_error = None;
+        if let Some(bus) = pipeline.bus() {
+            while let Some(msg) = bus.pop() {
+                if let Some(missing_plugin) = MissingPlugin::from_message(&msg) {
+                    missing_plugins.push(missing_plugin);
+                } else if let gst::MessageView::Error(err) = msg.view() {
+                    bus_error.get_or_insert_with(|| err.error());
+                }
+            }
+        }
+
+        // the bus is flushed when stopping, so this must happen after reading messages
+        if let Err(err) = pipeline.set_state(gst::State::Null) {
+            log::error!("failed to stop pipeline: {err}");
+        }
+
+        if !missing_plugins.is_empty() {
+            Error::MissingPlugins(missing_plugins)
+        } else if let Some(bus_error) = bus_error {
+            Error::Glib(bus_error)
+        } else {
+            err
+        }
     }
 
     /// Creates a new video based on an existing GStreamer pipeline and appsink.
-    /// Expects an `appsink` plugin with `caps=video/x-raw,format=NV12`.
+    /// Expects an `appsink` plugin with `caps=video/x-raw,format=NV12` (or any of `I420`, `P010_10LE`,
+    /// `P016_LE`, `Y444`, `RGBA`, or `BGRA`).
     ///
     /// **Note:** Many functions of [`Video`] assume a `playbin` pipeline.
     /// Non-`playbin` pipelines given here may not have full functionality.
     pub fn from_gst_pipeline(
         pipeline: gst::Pipeline,
         app_sink: gst_app::AppSink,
+    ) -> Result<Self, Error> {
+        let mut video = Self::from_prerolled(pipeline, app_sink, Duration::from_secs(5))?;
+        video
+            .set_paused(false)
+            .map_err(|err| Self::start_error(&video.pipeline(), err))?;
+        Ok(video)
+    }
+
+    /// Creates a new video which is paused once the media is ready to play (unless it is live).
+    pub(crate) fn from_prerolled(
+        pipeline: gst::Pipeline,
+        app_sink: gst_app::AppSink,
+        preroll_timeout: Duration,
     ) -> Result<Self, Error> {
         gst::init()?;
         static NEXT_ID: AtomicU64 = AtomicU64::new(0);
         let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
 
-        let pad = app_sink.pads().first().cloned().unwrap();
+        let pad = app_sink.static_pad("sink").ok_or(Error::Caps)?;
 
-        pipeline.set_state(gst::State::Playing)?;
-
-        // wait for up to 5 seconds until the decoder gets the source capabilities
-        pipeline.state(gst::ClockTime::from_seconds(5)).0?;
-
-        // extract resolution and framerate
-        // TODO(jazzfool): maybe we want to extract some other information too?
-        let caps = pad.current_caps().ok_or(Error::Caps)?;
-        let s = caps.structure(0).ok_or(Error::Caps)?;
-        let width = s.get::<i32>("width").map_err(|_| Error::Caps)?;
-        let height = s.get::<i32>("height").map_err(|_| Error::Caps)?;
-        // resolution should be mod4
-        let width = ((width + 4 - 1) / 4) * 4;
-        let framerate = s
-            .get::<gst::Fraction>("framerate")
-            .map_err(|_| Error::Caps)?;
-        let framerate = framerate.numer() as f64 / framerate.denom() as f64;
+        // let upstream hand over padded or cropped frames as-is, rather than copying them to remove the padding
+        pad.add_probe(gst::PadProbeType::QUERY_DOWNSTREAM, |_, info| {
+            if let Some(query) = info.query_mut() {
+                if let gst::QueryViewMut::Allocation(allocation) = query.view_mut() {
+                    allocation.add_allocation_meta::<gst_video::VideoMeta>(None);
+                    allocation.add_allocation_meta::<gst_video::VideoCropMeta>(None);
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
 
-        if framerate.is_nan()
-            || framerate.is_infinite()
-            || framerate < 0.0
-            || framerate.abs() < f64::EPSILON
-        {
-            return Err(Error::Framerate(framerate));
-        }
+        let (live, width, height, framerate) = Self::start(&pipeline, &pad, preroll_timeout)
+            .map_err(|err| Self::start_error(&pipeline, err))?;
 
         let duration = std::time::Duration::from_nanos(
             pipeline
@@ -249,28 +782,38 @@ impl Video {
                 .unwrap_or(0),
         );
 
-        let sync_av = pipeline.has_property("av-offset", None);
+        // the worker gets frames slightly ahead of time, so that they can be displayed exactly when they are due
+        app_sink.set_ts_offset(-(frame_to_time(FRAME_LOOKAHEAD, framerate).as_nanos() as i64));
+
+        let looping = Arc::new(Mutex::new(Looping {
+            mode: LoopMode::None,
+            count: 0,
+            ping_pong_reversed: false,
+            speed: 1.0,
+            segment_rate: 1.0,
+        }));
+        watch_segments(&pipeline, &looping)?;
 
-        // NV12 = 12bpp
-        let frame = Arc::new(Mutex::new(vec![
-            0u8;
-            (width as usize * height as usize * 3)
-                .div_ceil(2)
-        ]));
-        let upload_frame = Arc::new(AtomicBool::new(false));
+        let frame_queue = Arc::new(FrameQueue::new());
         let alive = Arc::new(AtomicBool::new(true));
-        let last_frame_time = Arc::new(Mutex::new(Instant::now()));
-        let paused = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(!live));
 
-        let frame_ref = Arc::clone(&frame);
-        let upload_frame_ref = Arc::clone(&upload_frame);
+        let frame_queue_ref = Arc::clone(&frame_queue);
         let alive_ref = Arc::clone(&alive);
-        let last_frame_time_ref = Arc::clone(&last_frame_time);
         let paused_ref = Arc::clone(&paused);
+        let (frame_tap, tap_receiver) = std::sync::mpsc::channel();
 
         let worker = std::thread::spawn(move || {
+            let mut last_buffer = None;
+            let mut tap: Option<Tap> = None;
+            // frames reading the decoder's buffers in place, wherever they are held
+            let pins = Arc::new(AtomicUsize::new(0));
             while alive_ref.load(Ordering::Acquire) {
+                while let Ok(new_tap) = tap_receiver.try_recv() {
+                    tap = new_tap;
+                }
                 if let Err(gst::FlowError::Error) = (|| -> Result<(), gst::FlowError> {
+                    let epoch = frame_queue_ref.epoch();
                     let sample = if paused_ref.load(Ordering::SeqCst) {
                         app_sink
                             .try_pull_preroll(gst::ClockTime::from_mseconds(16))
@@ -281,18 +824,29 @@ impl Video {
                             .ok_or(gst::FlowError::Eos)?
                     };
 
-                    *last_frame_time_ref
-                        .lock()
-                        .map_err(|_| gst::FlowError::Error)? = Instant::now();
-
+                    // the preroll sample is pulled over and over while paused, but only needs to be queued once
                     let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
-                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
-
-                    let mut frame = frame_ref.lock().map_err(|_| gst::FlowError::Error)?;
-                    let frame_len = frame.len();
-                    frame.copy_from_slice(&map.as_slice()[..frame_len]);
-
-                    upload_frame_ref.swap(true, Ordering::SeqCst);
+                    let buffer_id = Some((buffer.as_ptr() as usize, buffer.pts(), epoch));
+                    if buffer_id == last_buffer {
+                        std::thread::sleep(Duration::from_millis(16));
+                        return Ok(());
+                    }
+                    last_buffer = buffer_id;
+
+                    // the frame keeps the sample's buffer mapped until it is dropped, so nothing is copied
+                    // (unless too many frames already hold on to the decoder's buffers)
+                    // appsink is a basesink, but isn't wrapped as one
+                    let latency = app_sink
+                        .dynamic_cast_ref::<gst_base::BaseSink>()
+                        .map_or(gst::ClockTime::ZERO, |sink| sink.latency());
+                    let frame = Frame::pinned(&sample, latency, &pins)
+                        .map_err(|_| gst::FlowError::Error)?;
+                    let frame = Arc::new(frame);
+                    // the tap never blocks, so a slow consumer misses frames rather than stalling playback
+                    if tap.as_mut().is_some_and(|tap| !tap.send(&frame)) {
+                        tap = None;
+                    }
+                    frame_queue_ref.push(frame, epoch);
 
                     Ok(())
                 })() {
@@ -304,7 +858,7 @@ impl Video {
         Ok(Video(RefCell::new(Internal {
             id,
 
-            bus: pipeline.bus().unwrap(),
+            bus: pipeline.bus().ok_or(Error::Bus)?,
             source: pipeline,
             alive,
             worker: Some(worker),
@@ -313,18 +867,26 @@ impl Video {
             height,
             framerate,
             duration,
-            speed: 1.0,
-            sync_av,
 
-            frame,
-            upload_frame,
-            last_frame_time,
+            frame_queue,
+            frame: None,
+            upload_frame: false,
+            frame_tap,
             paused,
-            looping: false,
+            looping,
             is_eos: false,
             restart_stream: false,
-            sync_av_avg: 0,
-            sync_av_counter: 0,
+
+            stream_uri: None,
+            metadata: Metadata::default(),
+            chapters: Vec::new(),
+            chapter: None,
+            buffering: None,
+            live,
+            seeking: false,
+            pending_step: None,
+            stepping: None,
+            tone_mapping: ToneMapping::default(),
         })))
     }
 
@@ -335,7 +897,18 @@ impl Video {
 
     /// Get the framerate of the video as frames per second.
     pub fn framerate(&self) -> f64 {
-        self.0.borrow().framerate
+        let framerate = self.0.borrow().framerate;
+        framerate.numer() as f64 / framerate.denom() as f64
+    }
+
+    /// Get the index of the frame displayed at a given time, based on the negotiated framerate.
+    pub fn time_to_frame(&self, time: std::time::Duration) -> u64 {
+        time_to_frame(time, self.0.borrow().framerate)
+    }
+
+    /// Get the time at which a given frame starts, based on the negotiated framerate.
+    pub fn frame_to_time(&self, frame: u64) -> std::time::Duration {
+        frame_to_time(frame, self.0.borrow().framerate)
     }
 
     /// Set the volume multiplier of the audio.
@@ -369,18 +942,43 @@ impl Video {
 
     /// Get if the media will loop or not.
     pub fn looping(&self) -> bool {
-        self.0.borrow().looping
+        lock(&self.0.borrow().looping).mode != LoopMode::None
     }
 
-    /// Set if the media will loop or not.
+    /// Set if the media will loop (forever) or not.
     pub fn set_looping(&mut self, looping: bool) {
-        self.0.get_mut().looping = looping;
+        let loop_mode = if looping {
+            LoopMode::Infinite
+        } else {
+            LoopMode::None
+        };
+        if let Err(err) = self.set_loop_mode(loop_mode) {
+            log::error!("failed to set loop mode: {err}");
+        }
+    }
+
+    /// Get how the media loops.
+    pub fn loop_mode(&self) -> LoopMode {
+        lock(&self.0.borrow().looping).mode
+    }
+
+    /// Set how the media loops, restarting the loop count.
+    ///
+    /// Loop modes other than [`LoopMode::None`] start a new segment from the current position,
+    /// or from the start of the region for [`LoopMode::Region`] if currently outside of it.
+    /// The loop mode is left unchanged if it fails (e.g., with [`Error::LoopRegion`] for an empty region).
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) -> Result<(), Error> {
+        self.0.get_mut().set_loop_mode(loop_mode)
+    }
+
+    /// Get the number of times the media has looped since the loop mode was set.
+    pub fn loop_count(&self) -> u32 {
+        lock(&self.0.borrow().looping).count
     }
 
     /// Set if the media is paused or not.
-    pub fn set_paused(&mut self, paused: bool) {
-        let inner = self.0.get_mut();
-        inner.set_paused(paused);
+    pub fn set_paused(&mut self, paused: bool) -> Result<(), Error> {
+        self.0.get_mut().set_paused(paused)
     }
 
     /// Get if the media is paused or not.
@@ -401,20 +999,82 @@ impl Video {
         self.0.get_mut().set_speed(speed)
     }
 
+    /// Steps forward (or backward, if negative) by a number of frames.
+    ///
+    /// This pauses the media if it is not already paused. Stepping happens in the background;
+    /// [`VideoEvent::StepDone`](crate::VideoEvent::StepDone) is published once the new frame is displayed.
+    pub fn step_frames(&mut self, frames: i64) -> Result<(), Error> {
+        self.0.get_mut().step_frames(frames)
+    }
+
+    /// Get the timing of the frame currently displayed, if any.
+    ///
+    /// Unlike [`Video::position`], which is where the pipeline is at, this is the frame which is actually on screen.
+    pub fn displayed_frame(&self) -> Option<FrameInfo> {
+        self.0
+            .borrow()
+            .frame
+            .as_ref()
+            .and_then(|frame| frame.info())
+    }
+
     /// Get the current playback speed.
     pub fn speed(&self) -> f64 {
-        self.0.borrow().speed
+        lock(&self.0.borrow().looping).speed
     }
 
     /// Get the current playback position in time.
+    /// For live sources, this is the time since playback started.
     pub fn position(&self) -> std::time::Duration {
-        std::time::Duration::from_nanos(
-            self.0
-                .borrow()
-                .source
-                .query_position::<gst::ClockTime>()
-                .map_or(0, |pos| pos.nseconds()),
-        )
+        self.0.borrow().position()
+    }
+
+    /// Get if the media is a live source (e.g., a camera or broadcast stream).
+    ///
+    /// Live sources have no duration, their position is the time since playback started,
+    /// and they can't be seeked, sped up, or stepped through.
+    pub fn is_live(&self) -> bool {
+        self.0.borrow().live
+    }
+
+    /// Get the latency of the pipeline (i.e., how far behind the source playback is), if known.
+    pub fn latency(&self) -> Option<std::time::Duration> {
+        let mut query = gst::query::Latency::new();
+        self.0
+            .borrow()
+            .source
+            .query(&mut query)
+            .then(|| std::time::Duration::from_nanos(query.result().1.nseconds()))
+    }
+
+    /// Get the buffering progress in percent, if the media is currently buffering.
+    ///
+    /// Playback automatically holds while buffering and resumes afterwards.
+    pub fn buffering(&self) -> Option<i32> {
+        self.0.borrow().buffering
+    }
+
+    /// Get the ranges of the media which have been downloaded (e.g., to draw them on a seek bar).
+    ///
+    /// Only network streams report this.
+    pub fn buffered_ranges(&self) -> Vec<std::ops::Range<std::time::Duration>> {
+        let inner = self.0.borrow();
+        let mut query = gst::query::Buffering::new(gst::Format::Percent);
+        if !inner.source.query(&mut query) {
+            return Vec::new();
+        }
+
+        let to_time = |value: gst::GenericFormattedValue| match value {
+            gst::GenericFormattedValue::Percent(Some(percent)) => {
+                inner.duration.mul_f64(percent.ratio() as f64)
+            }
+            _ => std::time::Duration::ZERO,
+        };
+        query
+            .ranges()
+            .into_iter()
+            .map(|(start, stop)| to_time(start)..to_time(stop))
+            .collect()
     }
 
     /// Get the media duration.
@@ -422,7 +1082,7 @@ impl Video {
         self.0.borrow().duration
     }
 
-    /// Restarts a stream; seeks to the first frame and unpauses, sets the `eos` flag to false.
+    /// Restarts a stream; seeks to the first frame (or the start of the [`LoopMode::Region`]) and unpauses, sets the `eos` flag to false.
     pub fn restart_stream(&mut self) -> Result<(), Error> {
         self.0.get_mut().restart_stream()
     }
@@ -433,8 +1093,7 @@ impl Video {
         let inner = self.0.get_mut();
         inner.source.set_state(gst::State::Ready)?;
         inner.source.set_property("suburi", url.as_str());
-        inner.set_paused(paused);
-        Ok(())
+        inner.set_paused(paused)
     }
 
     /// Get the current subtitle URL.
@@ -474,91 +1133,211 @@ impl Video {
         flags_class.is_set_by_nick(&flags, "text")
     }
 
-    /// Get the underlying GStreamer pipeline.
-    pub fn pipeline(&self) -> gst::Pipeline {
-        self.0.borrow().source.clone()
+    /// Get the audio, video, or text streams available in the media.
+    /// This is always empty for pipelines other than `playbin`.
+    pub fn tracks(&self, kind: TrackKind) -> Vec<Track> {
+        let source = &self.0.borrow().source;
+        (0..kind.count(source))
+            .map(|index| Track::query(source, kind, index))
+            .collect()
     }
 
-    /// Generates a list of thumbnails based on a set of positions in the media, downscaled by a given factor.
+    /// Get the index of the currently selected stream of a given kind, if any.
+    pub fn current_track(&self, kind: TrackKind) -> Option<i32> {
+        kind.current(&self.0.borrow().source)
+    }
+
+    /// Switch to a different stream of a given kind (e.g., to change the audio language).
+    /// Playback continues from the current position.
+    pub fn set_track(&mut self, kind: TrackKind, index: i32) -> Result<(), Error> {
+        if !kind.select(&self.0.get_mut().source, index) {
+            return Err(Error::Track(kind, index));
+        }
+        Ok(())
+    }
+
+    /// Get if the video is high dynamic range (i.e., uses the PQ or HLG transfer function).
+    pub fn is_hdr(&self) -> bool {
+        self.current_frame()
+            .is_some_and(|frame| frame.color.transfer.is_hdr())
+    }
+
+    /// Get the frame which was last presented, leaving any queued frames for the renderer.
+    fn current_frame(&self) -> Option<Arc<Frame>> {
+        self.0.borrow().frame.clone()
+    }
+
+    /// Get a copy of the frame currently displayed, at full resolution.
     ///
-    /// Slow; only needs to be called once for each instance.
-    /// It's best to call this at the very start of playback, otherwise the position may shift.
-    pub fn thumbnails<I>(
-        &mut self,
-        positions: I,
-        downscale: NonZeroU8,
-    ) -> Result<Vec<img::Handle>, Error>
+    /// Colors are converted the same way as on screen (including tone mapping of HDR video to SDR).
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        let frame = self.current_frame().ok_or(Error::NoFrame)?;
+        let (width, height, rgba) = to_rgba(&frame, Resize::ORIGINAL, self.tone_mapping());
+        Ok(Snapshot {
+            width,
+            height,
+            pixels: rgba.into(),
+            info: frame.info(),
+        })
+    }
+
+    /// Start handing decoded frames to the returned receiver as they are pulled from the pipeline (e.g., for analysis).
+    ///
+    /// Frames aren't converted, and are handed over before they are displayed. If `tap.capacity` frames
+    /// are already waiting, new frames are dropped instead, so playback never waits for the receiver.
+    /// Received frames may pin the decoder's buffers, so they must be dropped promptly (see [`DecodedFrame`]).
+    /// This replaces any previous frame tap; dropping the receiver removes it.
+    pub fn frame_channel(&mut self, tap: FrameTap) -> std::sync::mpsc::Receiver<DecodedFrame> {
+        let (tap, receiver) = Tap::new(tap);
+        let _ = self.0.get_mut().frame_tap.send(Some(tap));
+        receiver
+    }
+
+    /// Start calling `callback` with decoded frames on a background thread, like [`Video::frame_channel`].
+    ///
+    /// Frames arriving while `callback` is busy wait or are dropped according to `tap.capacity`.
+    pub fn set_frame_callback<F>(&mut self, tap: FrameTap, mut callback: F)
     where
-        I: IntoIterator<Item = Position>,
+        F: FnMut(DecodedFrame) + Send + 'static,
     {
-        let downscale = u8::from(downscale) as u32;
+        let receiver = self.frame_channel(tap);
+        std::thread::spawn(move || {
+            for frame in receiver {
+                callback(frame);
+            }
+        });
+    }
 
-        let paused = self.paused();
-        let muted = self.muted();
-        let pos = self.position();
-
-        self.set_paused(false);
-        self.set_muted(true);
-
-        let out = {
-            let inner = self.0.borrow();
-            let width = inner.width;
-            let height = inner.height;
-            positions
-                .into_iter()
-                .map(|pos| {
-                    inner.seek(pos, true)?;
-                    inner.upload_frame.store(false, Ordering::SeqCst);
-                    while !inner.upload_frame.load(Ordering::SeqCst) {
-                        std::hint::spin_loop();
-                    }
-                    Ok(img::Handle::from_rgba(
-                        inner.width as u32 / downscale,
-                        inner.height as u32 / downscale,
-                        yuv_to_rgba(
-                            &inner.frame.lock().map_err(|_| Error::Lock)?,
-                            width as _,
-                            height as _,
-                            downscale,
-                        ),
-                    ))
-                })
-                .collect()
-        };
+    /// Stop handing decoded frames to the frame channel or callback, if any.
+    pub fn remove_frame_tap(&mut self) {
+        let _ = self.0.get_mut().frame_tap.send(None);
+    }
 
-        self.set_paused(paused);
-        self.set_muted(muted);
-        self.seek(pos, true)?;
+    /// Get how HDR video is fit into the range of the display.
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.0.borrow().tone_mapping
+    }
 
-        out
+    /// Set how HDR video is fit into the range of the display.
+    /// The default is [`ToneMapping::Bt2390`]; SDR video is never affected.
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.0.get_mut().tone_mapping = tone_mapping;
     }
-}
 
-fn yuv_to_rgba(yuv: &[u8], width: u32, height: u32, downscale: u32) -> Vec<u8> {
-    let uv_start = width * height;
-    let mut rgba = vec![];
+    /// Get the metadata of the media (title, codecs, cover art, etc.).
+    ///
+    /// This is updated as tags are received during playback.
+    pub fn metadata(&self) -> Metadata {
+        self.0.borrow().metadata.clone()
+    }
 
-    for y in 0..height / downscale {
-        for x in 0..width / downscale {
-            let x_src = x * downscale;
-            let y_src = y * downscale;
+    /// Get the chapters of the media, if it has a table of contents.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        self.0.borrow().chapters.clone()
+    }
 
-            let uv_i = uv_start + width * (y_src / 2) + x_src / 2 * 2;
+    /// Get the index of the chapter currently playing, if any.
+    pub fn current_chapter(&self) -> Option<usize> {
+        let inner = self.0.borrow();
+        chapter_at(&inner.chapters, inner.position())
+    }
 
-            let y = yuv[(y_src * width + x_src) as usize] as f32;
-            let u = yuv[uv_i as usize] as f32;
-            let v = yuv[(uv_i + 1) as usize] as f32;
+    /// Jumps to the start of a given chapter.
+    pub fn seek_to_chapter(&mut self, index: usize) -> Result<(), Error> {
+        let inner = self.0.get_mut();
+        let start = inner
+            .chapters
+            .get(index)
+            .ok_or(Error::Chapter(index))?
+            .start;
+        inner.seek(start, true)
+    }
 
-            let r = 1.164 * (y - 16.0) + 1.596 * (v - 128.0);
-            let g = 1.164 * (y - 16.0) - 0.813 * (v - 128.0) - 0.391 * (u - 128.0);
-            let b = 1.164 * (y - 16.0) + 2.018 * (u - 128.0);
+    /// Jumps to the start of the chapter following the current position.
+    /// Returns `false` (without seeking) if there is no next chapter, e.g., while in the last one.
+    pub fn next_chapter(&mut self) -> Result<bool, Error> {
+        let inner = self.0.get_mut();
+        let position = inner.position();
+        let Some(index) = inner
+            .chapters
+            .iter()
+            .position(|chapter| chapter.start > position)
+        else {
+            return Ok(false);
+        };
+        self.seek_to_chapter(index)?;
+        Ok(true)
+    }
 
-            rgba.push(r as u8);
-            rgba.push(g as u8);
-            rgba.push(b as u8);
-            rgba.push(0xFF);
+    /// Jumps to the start of the chapter preceding the current one (or the start of the first chapter).
+    /// Returns `false` (without seeking) if the media has no chapters.
+    pub fn previous_chapter(&mut self) -> Result<bool, Error> {
+        let inner = self.0.get_mut();
+        if inner.chapters.is_empty() {
+            return Ok(false);
         }
+        let position = inner.position();
+        let index = inner
+            .chapters
+            .iter()
+            .rposition(|chapter| chapter.start <= position)
+            .map_or(0, |index| index.saturating_sub(1));
+        self.seek_to_chapter(index)?;
+        Ok(true)
+    }
+
+    /// Get the underlying GStreamer pipeline.
+    pub fn pipeline(&self) -> gst::Pipeline {
+        self.0.borrow().source.clone()
+    }
+
+    /// Starts generating thumbnails at a set of positions in the media, resized to fit a given size.
+    ///
+    /// The media is decoded again with a separate pipeline on a background thread, so playback isn't affected.
+    /// Each thumbnail fails with [`Error::Timeout`] if it takes longer than `timeout`.
+    pub fn thumbnails<I>(&self, positions: I, resize: Resize, timeout: Duration) -> Thumbnails
+    where
+        I: IntoIterator<Item = Position>,
+    {
+        let inner = self.0.borrow();
+        Thumbnails::spawn(
+            inner.uri(),
+            positions.into_iter().collect(),
+            resize,
+            inner.tone_mapping,
+            timeout,
+        )
     }
 
-    return rgba;
+    /// Starts generating thumbnails every `interval`, resized to fit a given size,
+    /// and packs them into sprite sheets with a grid of `columns` by `rows` thumbnails.
+    ///
+    /// Like [`Video::thumbnails`], this uses a separate pipeline, so playback isn't affected.
+    /// Thumbnails which fail or take longer than `timeout` are left blank.
+    /// Fails with [`Error::Interval`] if `interval` is zero.
+    pub fn trickplay(
+        &self,
+        interval: Duration,
+        resize: Resize,
+        columns: u32,
+        rows: u32,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Trickplay, Error>> {
+        let inner = self.0.borrow();
+        let tile_size = resize.size(inner.width as u32, inner.height as u32);
+        let trickplay =
+            Trickplay::new(interval, inner.duration, tile_size, columns, rows).map(|layout| {
+                Trickplay::spawn(inner.uri(), layout, resize, inner.tone_mapping, timeout)
+            });
+        async move { trickplay?.await }
+    }
+}
+
+/// Gets the format and layout of the video described by `caps`.
+pub(crate) fn video_format(
+    caps: &gst::CapsRef,
+) -> Result<(PixelFormat, gst_video::VideoInfo), Error> {
+    let format = PixelFormat::from_caps(caps)?;
+    let info = gst_video::VideoInfo::from_caps(caps).map_err(|_| Error::Caps)?;
+    Ok((format, info))
 }
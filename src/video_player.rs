@@ -1,13 +1,21 @@
-use crate::{pipeline::VideoPrimitive, video::Video};
+use crate::{
+    chapter::chapter_at,
+    pipeline::VideoPrimitive,
+    video::{lock, Video, LOOPED_MESSAGE},
+    Chapter, MissingPlugin, VideoEvent,
+};
 use gstreamer as gst;
+use gstreamer::prelude::*;
 use iced::{
     advanced::{self, graphics::core::event::Status, layout, widget, Widget},
     Element,
 };
 use iced_wgpu::primitive::Renderer as PrimitiveRenderer;
-use log::error;
+use log::{error, warn};
+use std::sync::Arc;
 use std::{marker::PhantomData, sync::atomic::Ordering};
-use std::{sync::Arc, time::Instant};
+
+type ErrorCallback<'a, Message> = Box<dyn Fn(&glib::Error) -> Message + 'a>;
 
 /// Video player widget which displays the current frame of a [`Video`](crate::Video).
 pub struct VideoPlayer<'a, Message, Theme = iced::Theme, Renderer = iced::Renderer>
@@ -20,7 +28,11 @@ where
     height: iced::Length,
     on_end_of_stream: Option<Message>,
     on_new_frame: Option<Message>,
-    on_error: Option<Box<dyn Fn(&glib::Error) -> Message + 'a>>,
+    on_error: Option<ErrorCallback<'a, Message>>,
+    on_chapter: Option<Box<dyn Fn(usize) -> Message + 'a>>,
+    on_loop: Option<Box<dyn Fn(u32) -> Message + 'a>>,
+    on_buffering: Option<Box<dyn Fn(i32) -> Message + 'a>>,
+    on_event: Option<Box<dyn Fn(VideoEvent) -> Message + 'a>>,
     _phantom: PhantomData<(Theme, Renderer)>,
 }
 
@@ -38,6 +50,10 @@ where
             on_end_of_stream: None,
             on_new_frame: None,
             on_error: None,
+            on_chapter: None,
+            on_loop: None,
+            on_buffering: None,
+            on_event: None,
             _phantom: Default::default(),
         }
     }
@@ -91,6 +107,96 @@ where
             ..self
         }
     }
+
+    /// Message to send when playback enters a different chapter, given the index of the new chapter.
+    pub fn on_chapter<F>(self, on_chapter: F) -> Self
+    where
+        F: 'a + Fn(usize) -> Message,
+    {
+        VideoPlayer {
+            on_chapter: Some(Box::new(on_chapter)),
+            ..self
+        }
+    }
+
+    /// Message to send each time the video loops, given the number of loops so far.
+    pub fn on_loop<F>(self, on_loop: F) -> Self
+    where
+        F: 'a + Fn(u32) -> Message,
+    {
+        VideoPlayer {
+            on_loop: Some(Box::new(on_loop)),
+            ..self
+        }
+    }
+
+    /// Message to send when a network stream reports buffering progress, given the percentage buffered.
+    /// Playback resumes once this reaches `100`.
+    pub fn on_buffering<F>(self, on_buffering: F) -> Self
+    where
+        F: 'a + Fn(i32) -> Message,
+    {
+        VideoPlayer {
+            on_buffering: Some(Box::new(on_buffering)),
+            ..self
+        }
+    }
+
+    /// Message to send for every [`VideoEvent`] (state changes, warnings, tags, etc.).
+    ///
+    /// This is called in addition to any of the more specific callbacks above.
+    pub fn on_event<F>(self, on_event: F) -> Self
+    where
+        F: 'a + Fn(VideoEvent) -> Message,
+    {
+        VideoPlayer {
+            on_event: Some(Box::new(on_event)),
+            ..self
+        }
+    }
+
+    fn publish(&self, shell: &mut advanced::Shell<'_, Message>, event: VideoEvent)
+    where
+        Message: Clone,
+    {
+        match &event {
+            VideoEvent::NewFrame => {
+                if let Some(on_new_frame) = self.on_new_frame.clone() {
+                    shell.publish(on_new_frame);
+                }
+            }
+            VideoEvent::EndOfStream => {
+                if let Some(on_end_of_stream) = self.on_end_of_stream.clone() {
+                    shell.publish(on_end_of_stream);
+                }
+            }
+            VideoEvent::Error(err) => {
+                if let Some(ref on_error) = self.on_error {
+                    shell.publish(on_error(err));
+                }
+            }
+            VideoEvent::ChapterChanged(chapter) => {
+                if let Some(ref on_chapter) = self.on_chapter {
+                    shell.publish(on_chapter(*chapter));
+                }
+            }
+            VideoEvent::Looped(loop_count) => {
+                if let Some(ref on_loop) = self.on_loop {
+                    shell.publish(on_loop(*loop_count));
+                }
+            }
+            VideoEvent::Buffering(percent) => {
+                if let Some(ref on_buffering) = self.on_buffering {
+                    shell.publish(on_buffering(*percent));
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(ref on_event) = self.on_event {
+            shell.publish(on_event(event));
+        }
+    }
 }
 
 impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer>
@@ -167,25 +273,17 @@ where
 
         let drawing_bounds = iced::Rectangle::new(position, final_size);
 
-        let upload_frame = inner.upload_frame.swap(false, Ordering::SeqCst);
-
-        if upload_frame {
-            let last_frame_time = inner
-                .last_frame_time
-                .lock()
-                .map(|time| time.clone())
-                .unwrap_or_else(|_| Instant::now());
-            inner.set_av_offset(Instant::now() - last_frame_time);
-        }
+        // the frame which is due now is displayed, so that frames are paced by their timestamps
+        inner.take_frame();
+        let upload_frame = std::mem::take(&mut inner.upload_frame);
 
         renderer.draw_primitive(
             drawing_bounds,
             VideoPrimitive::new(
                 inner.id,
                 Arc::clone(&inner.alive),
-                Arc::clone(&inner.frame),
-                (inner.width as _, inner.height as _),
-                upload_frame,
+                inner.frame.clone().filter(|_| upload_frame),
+                inner.tone_mapping,
             ),
         );
     }
@@ -204,55 +302,189 @@ where
         let mut inner = self.video.0.borrow_mut();
 
         if let iced::Event::Window(iced::window::Event::RedrawRequested(_)) = event {
-            if inner.restart_stream || (!inner.is_eos && !inner.paused.load(Ordering::SeqCst)) {
-                let mut restart_stream = false;
-                if inner.restart_stream {
-                    restart_stream = true;
-                    // Set flag to false to avoid potentially multiple seeks
-                    inner.restart_stream = false;
-                }
-                let mut eos_pause = false;
-
-                while let Some(msg) = inner
-                    .bus
-                    .pop_filtered(&[gst::MessageType::Error, gst::MessageType::Eos])
-                {
-                    match msg.view() {
-                        gst::MessageView::Error(err) => {
-                            error!("bus returned an error: {err}");
-                            if let Some(ref on_error) = self.on_error {
-                                shell.publish(on_error(&err.error()))
-                            };
+            let playing =
+                inner.restart_stream || (!inner.is_eos && !inner.paused.load(Ordering::SeqCst));
+
+            let mut restart_stream = false;
+            if inner.restart_stream {
+                restart_stream = true;
+                // Set flag to false to avoid potentially multiple seeks
+                inner.restart_stream = false;
+            }
+            let mut eos_pause = false;
+
+            // the bus is drained even while paused so that events aren't delayed until playback resumes
+            while let Some(msg) = inner.bus.pop() {
+                match msg.view() {
+                    gst::MessageView::Error(err) => {
+                        error!("bus returned an error: {err}");
+                        self.publish(shell, VideoEvent::Error(err.error()));
+                    }
+                    gst::MessageView::Warning(warning) => {
+                        warn!("bus returned a warning: {warning}");
+                        self.publish(shell, VideoEvent::Warning(warning.error()));
+                    }
+                    gst::MessageView::Eos(_eos) => {
+                        self.publish(shell, VideoEvent::EndOfStream);
+                        // only reached when looping if segment seeks aren't supported
+                        let looped = {
+                            let mut looping = lock(&inner.looping);
+                            looping.loops_remaining().then(|| {
+                                looping.count += 1;
+                                looping.count
+                            })
+                        };
+                        if let Some(loop_count) = looped {
+                            restart_stream = true;
+                            self.publish(shell, VideoEvent::Looped(loop_count));
+                        } else {
+                            eos_pause = true;
                         }
-                        gst::MessageView::Eos(_eos) => {
-                            if let Some(on_end_of_stream) = self.on_end_of_stream.clone() {
-                                shell.publish(on_end_of_stream);
+                    }
+                    // only reached if the pipeline already had a sync handler, so the segment wasn't looped yet
+                    gst::MessageView::SegmentDone(_) => {
+                        let looped = lock(&inner.looping).loop_segment(&inner.source);
+                        match looped {
+                            Ok(Some(loop_count)) => {
+                                self.publish(shell, VideoEvent::Looped(loop_count))
                             }
-                            if inner.looping {
-                                restart_stream = true;
-                            } else {
+                            Ok(None) => {
+                                self.publish(shell, VideoEvent::EndOfStream);
                                 eos_pause = true;
                             }
+                            Err(err) => error!("cannot loop stream (can't seek): {err:#?}"),
                         }
+                    }
+                    // segments are looped as soon as they are done, which is reported afterwards
+                    gst::MessageView::Application(application) => match application.structure() {
+                        Some(s) if s.has_name(LOOPED_MESSAGE) => match s.get::<u32>("count") {
+                            Ok(loop_count) => self.publish(shell, VideoEvent::Looped(loop_count)),
+                            Err(_) => {
+                                self.publish(shell, VideoEvent::EndOfStream);
+                                eos_pause = true;
+                            }
+                        },
                         _ => {}
+                    },
+                    // only the pipeline as a whole is of interest, not its individual elements
+                    gst::MessageView::StateChanged(state_changed)
+                        if msg.src() == Some(inner.source.upcast_ref::<gst::Object>()) =>
+                    {
+                        self.publish(
+                            shell,
+                            VideoEvent::StateChanged {
+                                old: state_changed.old(),
+                                new: state_changed.current(),
+                            },
+                        );
+                    }
+                    gst::MessageView::AsyncDone(_async_done) if inner.seeking => {
+                        inner.seeking = false;
+                        self.publish(shell, VideoEvent::SeekDone);
+                        if let Some(frames) = inner.pending_step.take() {
+                            if let Err(err) = inner.send_step(frames) {
+                                error!("cannot step frames: {err:#?}");
+                            }
+                        }
+                    }
+                    // stepping past the end leaves the same frame displayed
+                    gst::MessageView::StepDone(step_done) if step_done.get().5 => {
+                        inner.stepping = None;
+                        self.publish(shell, VideoEvent::StepDone);
                     }
+                    gst::MessageView::Tag(tag) => {
+                        let tags = tag.tags();
+                        inner.follow_uri();
+                        inner.metadata.merge(&tags);
+                        self.publish(shell, VideoEvent::Tags(tags));
+                    }
+                    gst::MessageView::Toc(toc) => {
+                        let (toc, _) = toc.toc();
+                        inner.follow_uri();
+                        inner.chapters = Chapter::from_toc(&toc, inner.duration);
+                    }
+                    // the media may change without recreating the video (e.g., in a playlist)
+                    gst::MessageView::StreamStart(_) | gst::MessageView::DurationChanged(_) => {
+                        inner.follow_uri();
+                        let duration = inner.duration;
+                        inner.update_duration();
+                        if inner.duration != duration {
+                            self.publish(shell, VideoEvent::DurationChanged(inner.duration));
+                        }
+                    }
+                    gst::MessageView::Buffering(buffering) => {
+                        let percent = buffering.percent();
+                        inner.set_buffering(percent);
+                        self.publish(shell, VideoEvent::Buffering(percent));
+                    }
+                    gst::MessageView::StreamCollection(collection) => {
+                        self.publish(
+                            shell,
+                            VideoEvent::StreamCollection(collection.stream_collection()),
+                        );
+                    }
+                    gst::MessageView::Element(element) => {
+                        if let Some(missing_plugin) = MissingPlugin::from_message(&msg) {
+                            warn!("missing plugin: {missing_plugin}");
+                            self.publish(shell, VideoEvent::MissingPlugin(missing_plugin));
+                        } else if let Some(structure) = element.structure() {
+                            self.publish(shell, VideoEvent::Element(structure.to_owned()));
+                        }
+                    }
+                    _ => {}
                 }
+            }
 
-                // Don't run eos_pause if restart_stream is true; fixes "pausing" after restarting a stream
-                if restart_stream {
-                    if let Err(err) = inner.restart_stream() {
-                        error!("cannot restart stream (can't seek): {err:#?}")
+            // the resolution and framerate of frames change along with the caps, so follow them
+            inner.take_frame();
+            let frame_caps = inner
+                .frame
+                .as_ref()
+                .map(|frame| (frame.width as i32, frame.height as i32, frame.framerate));
+            if let Some((width, height, framerate)) = frame_caps {
+                // variable framerates are `0/1`, in which case the framerate from the start is kept
+                if framerate.numer() > 0 {
+                    inner.framerate = framerate;
+                }
+                if (width, height) != (inner.width, inner.height) {
+                    inner.width = width;
+                    inner.height = height;
+                    shell.invalidate_layout();
+                    self.publish(shell, VideoEvent::Resized { width, height });
+                }
+            }
+
+            if inner.step_done() {
+                self.publish(shell, VideoEvent::StepDone);
+            }
+
+            // Don't run eos_pause if restart_stream is true; fixes "pausing" after restarting a stream
+            if restart_stream {
+                if let Err(err) = inner.restart_stream() {
+                    error!("cannot restart stream (can't seek): {err:#?}")
+                }
+            } else if eos_pause {
+                inner.is_eos = true;
+                if let Err(err) = inner.set_paused(true) {
+                    error!("cannot pause at end of stream: {err:#?}");
+                }
+            }
+
+            if playing && !inner.chapters.is_empty() {
+                let chapter = chapter_at(&inner.chapters, inner.position());
+                if chapter != inner.chapter {
+                    inner.chapter = chapter;
+                    if let Some(chapter) = chapter {
+                        self.publish(shell, VideoEvent::ChapterChanged(chapter));
                     }
-                } else if eos_pause {
-                    inner.is_eos = true;
-                    inner.set_paused(true);
                 }
+            }
 
-                if inner.upload_frame.load(Ordering::SeqCst) {
+            // keep polling while paused until the stepped frame arrives
+            if playing || inner.stepping.is_some() {
+                if inner.upload_frame {
                     shell.request_redraw(iced::window::RedrawRequest::NextFrame);
-                    if let Some(on_new_frame) = self.on_new_frame.clone() {
-                        shell.publish(on_new_frame);
-                    }
+                    self.publish(shell, VideoEvent::NewFrame);
                 } else {
                     shell
                         .request_redraw(iced::window::RedrawRequest::At(std::time::Instant::now()));
@@ -0,0 +1,139 @@
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use iced_video_player::Video;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Elements needed to encode, serve, and play back the test media.
+const ELEMENTS: [&str; 4] = [
+    "videotestsrc",
+    "matroskamux",
+    "matroskademux",
+    "souphttpsrc",
+];
+
+/// Encodes two seconds of uncompressed video, which is large enough to take a while to download.
+fn test_media() -> Vec<u8> {
+    let path = std::env::temp_dir().join(format!(
+        "iced_video_player_buffering_{}.mkv",
+        std::process::id()
+    ));
+    let pipeline = gst::parse::launch(&format!(
+        "videotestsrc num-buffers=60 ! video/x-raw,format=I420,width=320,height=240,framerate=30/1 \
+         ! matroskamux ! filesink location={}",
+        path.display()
+    ))
+    .unwrap();
+    pipeline.set_state(gst::State::Playing).unwrap();
+    let msg = pipeline.bus().unwrap().timed_pop_filtered(
+        gst::ClockTime::from_seconds(10),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    pipeline.set_state(gst::State::Null).unwrap();
+    assert_eq!(msg.map(|msg| msg.type_()), Some(gst::MessageType::Eos));
+
+    let data = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    data
+}
+
+/// Serves `data` over HTTP at about `rate` bytes per second (supporting range requests), returning its URL.
+fn serve_throttled(data: Vec<u8>, rate: usize) -> url::Url {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/video.mkv", listener.local_addr().unwrap());
+    let data = Arc::new(data);
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let data = Arc::clone(&data);
+            std::thread::spawn(move || {
+                let mut request = [0; 4096];
+                let len = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..len]);
+                let start = request
+                    .lines()
+                    .find_map(|line| {
+                        line.to_ascii_lowercase()
+                            .strip_prefix("range: bytes=")?
+                            .split('-')
+                            .next()?
+                            .parse()
+                            .ok()
+                    })
+                    .unwrap_or(0usize)
+                    .min(data.len());
+                let header = if start > 0 {
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {start}-{}/{}\r\n",
+                        data.len() - 1,
+                        data.len()
+                    )
+                } else {
+                    String::from("HTTP/1.1 200 OK\r\n")
+                } + &format!(
+                    "Content-Type: video/x-matroska\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+                    data.len() - start
+                );
+                if stream.write_all(header.as_bytes()).is_err() {
+                    return;
+                }
+                for chunk in data[start..].chunks(rate / 10) {
+                    if stream.write_all(chunk).is_err() {
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            });
+        }
+    });
+    url::Url::parse(&url).unwrap()
+}
+
+#[test]
+#[ignore = "serves media over a throttled local HTTP server, so it depends on timing"]
+fn throttled_stream_buffers() {
+    gst::init().unwrap();
+    if let Some(missing) = ELEMENTS
+        .into_iter()
+        .find(|element| gst::ElementFactory::find(element).is_none())
+    {
+        eprintln!("skipping, since the {missing} element is not installed");
+        return;
+    }
+
+    // about 6.9 MB, taking several seconds to download
+    let video = Video::new(&serve_throttled(test_media(), 1 << 20)).unwrap();
+    let bus = video.pipeline().bus().unwrap();
+
+    let mut progress = Vec::new();
+    let mut buffered = false;
+    while let Some(msg) = bus.timed_pop_filtered(
+        gst::ClockTime::from_seconds(10),
+        &[gst::MessageType::Buffering],
+    ) {
+        let gst::MessageView::Buffering(buffering) = msg.view() else {
+            unreachable!();
+        };
+        let percent = buffering.percent();
+        progress.push(percent);
+
+        let ranges = video.buffered_ranges();
+        for range in &ranges {
+            assert!(range.start <= range.end, "{ranges:?}");
+            assert!(range.end <= video.duration(), "{ranges:?}");
+        }
+        buffered |= ranges.iter().any(|range| range.start < range.end);
+
+        if percent == 100 {
+            break;
+        }
+    }
+
+    assert!(
+        progress.iter().any(|&percent| percent < 100),
+        "{progress:?}"
+    );
+    assert_eq!(progress.last(), Some(&100), "{progress:?}");
+    assert!(buffered, "no buffered ranges were reported");
+}
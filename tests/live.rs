@@ -0,0 +1,54 @@
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use iced_video_player::{Error, Position, TrackKind, Video};
+use std::time::Duration;
+
+fn live_video() -> Video {
+    gst::init().unwrap();
+    let pipeline = gst::parse::launch(
+        "videotestsrc is-live=true ! video/x-raw,format=RGBA,width=64,height=48,framerate=30/1 \
+         ! appsink name=iced_video caps=video/x-raw,format=RGBA",
+    )
+    .unwrap()
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+    let app_sink = pipeline
+        .by_name("iced_video")
+        .unwrap()
+        .downcast::<gst_app::AppSink>()
+        .unwrap();
+    Video::from_gst_pipeline(pipeline, app_sink).unwrap()
+}
+
+#[test]
+fn live_source_is_detected() {
+    let video = live_video();
+    assert!(video.is_live());
+    assert_eq!(video.size(), (64, 48));
+    assert_eq!(video.duration(), Duration::ZERO);
+    // live sources start playing right away
+    assert!(!video.paused());
+}
+
+#[test]
+fn live_source_cannot_seek() {
+    let mut video = live_video();
+    assert!(matches!(
+        video.seek(Position::Time(Duration::from_secs(1)), false),
+        Err(Error::Live)
+    ));
+    assert!(matches!(video.set_speed(2.0), Err(Error::Live)));
+    assert!(matches!(video.step_frames(1), Err(Error::Live)));
+}
+
+#[test]
+fn custom_pipeline_has_no_tracks() {
+    let mut video = live_video();
+    assert!(video.tracks(TrackKind::Audio).is_empty());
+    assert_eq!(video.current_track(TrackKind::Audio), None);
+    assert!(matches!(
+        video.set_track(TrackKind::Audio, 0),
+        Err(Error::Track(TrackKind::Audio, 0))
+    ));
+}